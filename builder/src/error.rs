@@ -0,0 +1,159 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::{DeriveInput, Visibility};
+
+use crate::field::SubBuilder;
+use crate::options::StructOptions;
+
+// build()が返すエラー型 (FooBuilderError) を作る
+pub struct ErrorFactory {
+    error_name: Ident,
+    // builderと同じvisibilityにする
+    vis: Visibility,
+    // VecやStringを持ってくるcrate。::stdか::alloc
+    alloc: TokenStream,
+    no_std: bool,
+    // builder(env)の時、apply_kv()で値を読めなかったフィールドを持つvariantを作る
+    env: bool,
+    // #[builder(sub_builder)]のフィールドごとに、中のbuilderのエラーを持つvariantを作る
+    sub_builders: Vec<SubBuilder>,
+}
+
+impl ErrorFactory {
+    pub fn new(
+        input: &DeriveInput,
+        options: &StructOptions,
+        sub_builders: Vec<SubBuilder>,
+    ) -> Self {
+        Self {
+            error_name: options.builder_error_name(input),
+            vis: options.builder_vis(input),
+            alloc: options.alloc_crate(),
+            no_std: options.no_std,
+            env: options.env.is_some(),
+            sub_builders,
+        }
+    }
+
+    pub fn build(&self) -> TokenStream {
+        let vis = &self.vis;
+        let error_name = &self.error_name;
+        let alloc = &self.alloc;
+        let variants = self.sub_builders.iter().map(|sub_builder| {
+            let variant = &sub_builder.variant;
+            let error_type = &sub_builder.error_type;
+            let docs = format!("The nested builder of `{}` failed.", sub_builder.field);
+            quote! {
+                #[doc = #docs]
+                #variant(#error_type)
+            }
+        });
+        let nested_fmt = self.sub_builders.iter().map(|sub_builder| {
+            let variant = &sub_builder.variant;
+            let field = &sub_builder.field;
+            quote! {
+                Self::#variant(error) => error.__fmt_at(&at(#field), f),
+            }
+        });
+
+        let (env_variant, env_fmt) = if self.env {
+            (
+                quote! {
+                    /// Some values given to `apply_kv()` or `from_env()` could not be parsed.
+                    InvalidValues {
+                        /// The fields whose values could not be parsed, with the parse error.
+                        errors: #alloc::vec::Vec<(&'static str, #alloc::string::String)>,
+                    },
+                },
+                quote! {
+                    Self::InvalidValues { errors } => {
+                        let errors = errors
+                            .iter()
+                            .map(|(field, message)| {
+                                #alloc::format!("{} cannot be parsed: {}", at(field), message)
+                            })
+                            .collect::<#alloc::vec::Vec<_>>();
+                        f.write_str(&errors.join(", "))
+                    }
+                },
+            )
+        } else {
+            (quote!(), quote!())
+        };
+
+        // no_stdでも、stdを使えるようにするfeatureがあればstd::error::Errorを実装する
+        let error_impl = if self.no_std {
+            quote! {
+                #[cfg(feature = "std")]
+                impl ::std::error::Error for #error_name {}
+            }
+        } else {
+            quote! {
+                impl ::std::error::Error for #error_name {}
+            }
+        };
+
+        quote! {
+            /// The error returned when `build()` fails.
+            #[derive(::core::fmt::Debug, ::core::clone::Clone, ::core::cmp::PartialEq, ::core::cmp::Eq)]
+            #vis enum #error_name {
+                /// Some required fields are not set.
+                MissingFields {
+                    /// The fields that are not set, in declaration order.
+                    fields: #alloc::vec::Vec<&'static str>,
+                },
+                // #[builder(validate = "...")]がフィールドに付いている時
+                /// A field did not pass validation.
+                InvalidField {
+                    /// The field that did not pass.
+                    field: &'static str,
+                    /// The message returned by the validation function.
+                    message: #alloc::string::String,
+                },
+                // #[builder(validate = "...")]がstructに付いている時
+                /// The built value did not pass validation.
+                Validation(#alloc::string::String),
+                #env_variant
+                #(#variants,)*
+            }
+
+            impl #error_name {
+                // sub_builderのエラーは、外側のフィールド名をpathとして前に付けて表示する
+                #[doc(hidden)]
+                pub fn __fmt_at(&self, path: &str, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                    let at = |field: &str| -> #alloc::string::String {
+                        if path.is_empty() {
+                            #alloc::string::String::from(field)
+                        } else {
+                            #alloc::format!("{}.{}", path, field)
+                        }
+                    };
+                    match self {
+                        Self::MissingFields { fields } => {
+                            let fields = fields.iter().map(|field| at(field)).collect::<#alloc::vec::Vec<_>>();
+                            match fields.as_slice() {
+                                [field] => ::core::write!(f, "{} is not set", field),
+                                fields => ::core::write!(f, "{} are not set", fields.join(", ")),
+                            }
+                        }
+                        Self::InvalidField { field, message } => {
+                            ::core::write!(f, "{} is invalid: {}", at(field), message)
+                        }
+                        Self::Validation(message) if path.is_empty() => f.write_str(message),
+                        Self::Validation(message) => ::core::write!(f, "{}: {}", path, message),
+                        #env_fmt
+                        #(#nested_fmt)*
+                    }
+                }
+            }
+
+            impl ::core::fmt::Display for #error_name {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                    self.__fmt_at("", f)
+                }
+            }
+
+            #error_impl
+        }
+    }
+}
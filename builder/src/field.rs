@@ -0,0 +1,525 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{
+    parse_quote, Attribute, Data, DeriveInput, Field, Fields, Index, Member, Path, PathArguments,
+    Result, Type, Visibility,
+};
+
+use crate::options::{
+    BuilderPattern, FieldDefault, FieldEnv, FieldOptions, MergeStrategy, StructOptions,
+    SubBuilderOptions,
+};
+use crate::{collection_item, is_option, option_inner, to_camel_case, Errors};
+
+// 元のstructのフィールド1つ分。#[builder(...)]の解釈はここでまとめて済ませる
+pub struct BuilderField {
+    // builderのフィールド名。tuple structなら_0, _1...
+    pub ident: Ident,
+    // 元のstructのフィールド。tuple structなら0, 1...
+    pub member: Member,
+    pub ty: Type,
+    pub options: FieldOptions,
+    pub setter_name: Ident,
+    pub setter_into: bool,
+    // setter(vis = "...")。setterやclear_<field>()など、値を変えるメソッドに付ける
+    pub setter_vis: Visibility,
+    // 未設定のままbuild()した時の値。Noneなら必須フィールド
+    pub unset_value: Option<TokenStream>,
+    // #[builder(each = "...")]の時、setterが1回で足す要素
+    pub each_item: Option<EachItem>,
+    pub sub_builder: Option<SubBuilder>,
+    // builder(env)の時、apply_kv()でこのフィールドの値を探すkey
+    pub env_key: Option<String>,
+    // Option<T>として扱うかどうか。builder(optional)とbuilder(required)で型より優先する
+    pub optional: bool,
+    // 元のフィールドの///。setterのドキュメントにそのまま使う
+    docs: Vec<Attribute>,
+    // 未設定のままbuild()した時にどうなるかの説明。setterのドキュメントの最後に足す
+    unset_doc: String,
+}
+
+// #[builder(sub_builder)]のフィールドの型 (Bar) から作った、BarBuilderとBarBuilderError
+#[derive(Clone)]
+pub struct SubBuilder {
+    pub field: String,
+    pub builder_type: Path,
+    pub error_type: Path,
+    // FooBuilderErrorの中で、このフィールドのエラーを持つvariant
+    pub variant: Ident,
+    pub pattern: BuilderPattern,
+}
+
+pub enum EachItem {
+    // Vec<T>やHashSet<T>などのT
+    Single(Type),
+    // HashMap<K, V>やBTreeMap<K, V>は、keyとvalueを別々の引数で受け取る
+    Pair(Type, Type),
+}
+
+impl BuilderField {
+    // 元のstructの全フィールド分を作る。名前のないフィールドには位置から名前を付ける
+    pub fn from_input(input: &DeriveInput, struct_options: &StructOptions) -> Result<Vec<Self>> {
+        let Data::Struct(data_struct) = &input.data else {
+            return Err(syn::Error::new(Span::call_site(), "Should be Struct"));
+        };
+        if let Fields::Unit = data_struct.fields {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Builder needs a struct with fields",
+            ));
+        }
+        // 最初に間違いが見つかったフィールドで止めず、全てのフィールドを調べる
+        let mut errors = Errors::default();
+        let fields = data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .filter_map(|(index, field)| errors.ok(Self::new(field, index, struct_options)))
+            .collect();
+        errors.finish_with(Ok(fields))
+    }
+
+    pub fn new(field: &Field, index: usize, struct_options: &StructOptions) -> Result<Self> {
+        let (ident, member) = match &field.ident {
+            Some(ident) => (ident.clone(), Member::Named(ident.clone())),
+            None => (
+                format_ident!("_{}", index, span = field.ty.span()),
+                Member::Unnamed(Index::from(index)),
+            ),
+        };
+        let ty = field.ty.clone();
+        let options = FieldOptions::from_field(field)?;
+
+        let setter_name = options.setter.name.clone().unwrap_or_else(|| {
+            struct_options.setter_prefix.as_ref().map_or_else(
+                || ident.clone(),
+                |prefix| format_ident!("{}{}", prefix, ident.unraw()),
+            )
+        });
+        let setter_into = options.setter.into || struct_options.setter_into;
+        let setter_vis = options
+            .setter
+            .vis
+            .clone()
+            .unwrap_or_else(|| parse_quote!(pub));
+
+        let optional = options.optional.unwrap_or_else(|| is_option(&ty));
+
+        // Option<T>のフィールドは、何もしなければNoneになる
+        // eachのコレクションは、1つも足さなければ空になる
+        let unset_value = options
+            .default_value(&member, struct_options.default)
+            .or_else(|| optional.then(|| quote!(::core::option::Option::None)))
+            .or_else(|| {
+                options
+                    .each
+                    .is_some()
+                    .then(|| quote!(::core::default::Default::default()))
+            });
+
+        let mut errors = Errors::default();
+        let each_item = options
+            .each
+            .as_ref()
+            .and_then(|each| match &each.item {
+                Some(item) => Some(EachItem::Single(item.clone())),
+                None => errors.ok(collection_item(&ty).ok_or_else(|| {
+                    syn::Error::new_spanned(
+                        &ty,
+                        r#"cannot tell the item type of this collection, use `each(name = "...", item = "...")`"#,
+                    )
+                })),
+            });
+
+        let sub_builder = options
+            .sub_builder
+            .as_ref()
+            .and_then(|sub_builder| errors.ok(SubBuilder::new(&ident, &ty, sub_builder)));
+
+        let unset_doc = match &options.default {
+            _ if options.sub_builder.is_some() => {
+                "Built with the nested builder when `build()` is called.".to_owned()
+            }
+            Some(FieldDefault::Trait) => "Optional: `Default::default()` if not set.".to_owned(),
+            Some(FieldDefault::Expr(_, source)) => format!("Optional: `{source}` if not set."),
+            None if struct_options.default => {
+                "Optional: taken from the `Default` of the whole struct if not set.".to_owned()
+            }
+            None if optional => "Optional: `None` if not set.".to_owned(),
+            None if options.each.is_some() => "Optional: empty if not set.".to_owned(),
+            None => "Required: this must be set before `build()`.".to_owned(),
+        };
+        let docs = field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("doc"))
+            .cloned()
+            .collect();
+
+        // eachやsub_builderのフィールドは、1つの文字列から値を作れないので扱わない
+        let env_key = match &options.env {
+            Some(FieldEnv::Name(name)) => Some(name.clone()),
+            Some(FieldEnv::Skip) => None,
+            None => (struct_options.env.is_some()
+                && !options.setter.skip
+                && options.each.is_none()
+                && options.sub_builder.is_none())
+            .then(|| ident.unraw().to_string().to_uppercase()),
+        };
+        if options.env.is_some() && struct_options.env.is_none() {
+            errors.push(syn::Error::new_spanned(
+                field,
+                "`env` on a field needs `builder(env)` on the struct",
+            ));
+        }
+
+        if options.setter.skip && unset_value.is_none() {
+            errors.push(syn::Error::new_spanned(
+                field,
+                "`setter(skip)` needs a default value for the field",
+            ));
+        }
+
+        errors.finish_with(Ok(Self {
+            ident,
+            member,
+            ty,
+            options,
+            setter_name,
+            setter_into,
+            setter_vis,
+            unset_value,
+            each_item,
+            sub_builder,
+            env_key,
+            optional,
+            docs,
+            unset_doc,
+        }))
+    }
+
+    pub fn name_str(&self) -> String {
+        self.ident.unraw().to_string()
+    }
+
+    // ドキュメントの中でフィールドを指す名前。tuple structなら`0`, `1`...
+    pub fn doc_name(&self) -> String {
+        match &self.member {
+            Member::Named(ident) => format!("`{}`", ident.unraw()),
+            Member::Unnamed(index) => format!("`{}`", index.index),
+        }
+    }
+
+    // フィールドを並べたドキュメント。`a`, `b`
+    pub fn doc_names<'a>(fields: impl Iterator<Item = &'a Self>) -> String {
+        fields.map(Self::doc_name).collect::<Vec<_>>().join(", ")
+    }
+
+    // 元のフィールドの///に、未設定の時にどうなるかを足したsetterのドキュメント
+    pub fn setter_docs(&self) -> TokenStream {
+        self.docs_with(&self.unset_doc)
+    }
+
+    // 元のフィールドの///の後に、段落を分けてnoteを足す
+    pub fn docs_with(&self, note: &str) -> TokenStream {
+        let docs = &self.docs;
+        let separator = (!docs.is_empty()).then(|| quote!(#[doc = ""]));
+        quote! {
+            #(#docs)*
+            #separator
+            #[doc = #note]
+        }
+    }
+
+    pub fn is_repeated(&self) -> bool {
+        self.each_item.is_some()
+    }
+
+    // sub_builderのフィールドは、中のbuilderが自分で未設定のフィールドを調べる
+    pub fn is_required(&self) -> bool {
+        self.sub_builder.is_none() && self.unset_value.is_none()
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        self.options.setter.skip
+    }
+
+    // builderの中での型。sub_builder以外はOptionで包んで、セットされたかどうかを持つ
+    pub fn builder_type(&self) -> TokenStream {
+        let ty = &self.ty;
+        if let Some(sub_builder) = &self.sub_builder {
+            let builder_type = &sub_builder.builder_type;
+            quote!(#builder_type)
+        } else {
+            quote!(::core::option::Option<#ty>)
+        }
+    }
+
+    // setterが受け取る値の型 (intoの場合はこれに変換できるもの)
+    pub fn setter_value_type(&self) -> Type {
+        match self.stripped_option() {
+            Some(inner) => inner.clone(),
+            None => self.ty.clone(),
+        }
+    }
+
+    // setterがOption<T>のTを受け取る時のT
+    // builder(optional)でもtype aliasの中身は分からないので、型がOption<T>と書かれている時だけ
+    fn stripped_option(&self) -> Option<&Type> {
+        option_inner(&self.ty).filter(|_| self.optional && self.options.setter.strip_option)
+    }
+
+    // setterの引数の型と、それをbuilderのフィールドに入れる時の値
+    pub fn setter_argument(&self) -> (TokenStream, TokenStream) {
+        let ident = &self.ident;
+        let ty = self.setter_value_type();
+        let strip_option = self.stripped_option().is_some();
+        let value = Self::into_value(ident, self.setter_into, strip_option);
+        (
+            Self::into_type(quote!(#ty), self.setter_into),
+            quote!(::core::option::Option::Some(#value)),
+        )
+    }
+
+    // From<Foo>で元の値を分解する時のパターン。builderに無いフィールドは捨てる
+    pub fn binding(&self) -> TokenStream {
+        let member = &self.member;
+        let ident = &self.ident;
+        match &self.member {
+            _ if self.is_skipped() => quote!(#member: _),
+            Member::Named(_) => quote!(#ident),
+            Member::Unnamed(_) => quote!(#member: #ident),
+        }
+    }
+
+    // bindingで取り出した値を、セット済みとしてbuilderのフィールドに入れる
+    pub fn stored_binding(&self) -> TokenStream {
+        let ident = &self.ident;
+        if self.sub_builder.is_some() {
+            quote!(#ident: ::core::convert::From::from(#ident))
+        } else {
+            quote!(#ident: ::core::option::Option::Some(#ident))
+        }
+    }
+
+    // is_set_<field>()やget_<field>()の名前。tuple structならis_set_0, get_1...
+    pub fn method_name(&self, prefix: &str) -> Ident {
+        match &self.member {
+            Member::Named(ident) => format_ident!("{}{}", prefix, ident.unraw()),
+            Member::Unnamed(index) => format_ident!("{}{}", prefix, index.index),
+        }
+    }
+
+    // merge()で、otherのこのフィールドをbuilderに入れる文
+    pub fn merge_field(&self, builder: TokenStream) -> TokenStream {
+        let ident = &self.ident;
+        if self.sub_builder.is_some() {
+            return quote! {
+                #builder.#ident.__merge_from(other.#ident);
+            };
+        }
+        // eachにitemを指定したコレクションはIntoIteratorとは限らないので、置き換えにしておく
+        let merge = self.options.merge.unwrap_or_else(|| {
+            match self
+                .options
+                .each
+                .as_ref()
+                .and_then(|each| each.item.as_ref())
+            {
+                Some(_) => MergeStrategy::Replace,
+                None => MergeStrategy::Append,
+            }
+        });
+        if self.is_repeated() && merge == MergeStrategy::Append {
+            let collection = Self::collection_mut(quote!(#builder.#ident));
+            quote! {
+                if let ::core::option::Option::Some(items) = other.#ident {
+                    ::core::iter::Extend::extend(#collection, items);
+                }
+            }
+        } else {
+            quote! {
+                if let ::core::option::Option::Some(value) = other.#ident {
+                    #builder.#ident = ::core::option::Option::Some(value);
+                }
+            }
+        }
+    }
+
+    // コレクション全体を受け取るsetterは、eachのsetterと名前が被る時だけ作らない
+    pub fn has_bulk_setter(&self) -> bool {
+        self.options
+            .each
+            .as_ref()
+            .map_or(true, |each| each.name != self.setter_name)
+    }
+
+    // setter(try_into)の時のtry_<field>()。変換できたら普通のsetterに渡す
+    pub fn try_setter(&self, receiver: TokenStream, output: TokenStream) -> Option<TokenStream> {
+        if !self.options.setter.try_into {
+            return None;
+        }
+        let ident = &self.ident;
+        let setter_name = &self.setter_name;
+        let try_setter_name = format_ident!("try_{}", setter_name.unraw());
+        let ty = self.setter_value_type();
+        let vis = &self.setter_vis;
+        let docs = self.docs_with(&format!(
+            "Like `{}()`, but converts the value with `TryInto` first.",
+            setter_name.unraw()
+        ));
+        Some(quote! {
+            #docs
+            #vis fn #try_setter_name<__Value: ::core::convert::TryInto<#ty>>(
+                #receiver,
+                #ident: __Value,
+            ) -> ::core::result::Result<#output, <__Value as ::core::convert::TryInto<#ty>>::Error> {
+                let #ident: #ty = ::core::convert::TryInto::try_into(#ident)?;
+                ::core::result::Result::Ok(self.#setter_name(#ident))
+            }
+        })
+    }
+
+    // #[builder(each = "arg")]のsetterの名前と引数
+    pub fn each_setter(&self) -> Option<(&Ident, TokenStream)> {
+        let name = &self.options.each.as_ref()?.name;
+        let args = match self.each_item.as_ref()? {
+            EachItem::Single(item) => {
+                let arg_type = Self::into_type(quote!(#item), self.setter_into);
+                quote!(#name: #arg_type)
+            }
+            EachItem::Pair(key, value) => {
+                let key_type = Self::into_type(quote!(#key), self.setter_into);
+                let value_type = Self::into_type(quote!(#value), self.setter_into);
+                quote!(key: #key_type, value: #value_type)
+            }
+        };
+        Some((name, args))
+    }
+
+    // eachのフィールドに複数まとめて足すextend_<field>()の名前と引数
+    pub fn extend_setter(&self) -> Option<(Ident, TokenStream)> {
+        let item_type = self.item_type()?;
+        let name = format_ident!("extend_{}", self.setter_name.unraw());
+        Some((
+            name,
+            quote!(items: impl ::core::iter::IntoIterator<Item = #item_type>),
+        ))
+    }
+
+    // extend_setterの引数を、collectionに全部足す文
+    pub fn extend_items(&self, collection: TokenStream) -> TokenStream {
+        let collection = Self::collection_mut(collection);
+        quote! {
+            ::core::iter::Extend::extend(#collection, items);
+        }
+    }
+
+    // まだセットされていなければ空のコレクションを作ってから足す
+    fn collection_mut(collection: TokenStream) -> TokenStream {
+        quote! {
+            ::core::option::Option::get_or_insert_with(&mut #collection, ::core::default::Default::default)
+        }
+    }
+
+    fn item_type(&self) -> Option<TokenStream> {
+        match self.each_item.as_ref()? {
+            EachItem::Single(item) => Some(quote!(#item)),
+            EachItem::Pair(key, value) => Some(quote!((#key, #value))),
+        }
+    }
+
+    // each_setterの引数を、collectionに1つ足す文
+    pub fn extend_item(&self, collection: TokenStream) -> TokenStream {
+        let item = match &self.each_item {
+            Some(EachItem::Single(_)) => {
+                let name = &self.options.each.as_ref().unwrap().name;
+                Self::into_value(name, self.setter_into, false)
+            }
+            Some(EachItem::Pair(..)) => {
+                let key_value = Self::into_value(&format_ident!("key"), self.setter_into, false);
+                let value_value =
+                    Self::into_value(&format_ident!("value"), self.setter_into, false);
+                quote!((#key_value, #value_value))
+            }
+            None => unreachable!("extend_item is only called for `each` fields"),
+        };
+        let item_type = self.item_type();
+        let collection = Self::collection_mut(collection);
+        // Vec<T>はExtend<&T>も実装しているので、要素の型を明示する
+        quote! {
+            ::core::iter::Extend::extend(#collection, ::core::iter::once::<#item_type>(#item));
+        }
+    }
+
+    fn into_type(ty: TokenStream, into: bool) -> TokenStream {
+        if into {
+            quote!(impl ::core::convert::Into<#ty>)
+        } else {
+            ty
+        }
+    }
+
+    fn into_value(ident: &Ident, into: bool, wrap_some: bool) -> TokenStream {
+        let value = if into {
+            quote!(::core::convert::Into::into(#ident))
+        } else {
+            quote!(#ident)
+        };
+        if wrap_some {
+            quote!(::core::option::Option::Some(#value))
+        } else {
+            value
+        }
+    }
+}
+
+impl SubBuilder {
+    // crate::bar::Bar<T> -> crate::bar::BarBuilder<T>とcrate::bar::BarBuilderError
+    // sub_builder(builder = "...")があればその型を使い、エラーの型はそこから作る
+    fn new(ident: &Ident, ty: &Type, options: &SubBuilderOptions) -> Result<Self> {
+        let error = || {
+            syn::Error::new_spanned(
+                ty,
+                "`sub_builder` needs a type that derives Builder, like `Bar` or `path::to::Bar<T>`",
+            )
+        };
+        let Type::Path(type_path) = ty else {
+            return Err(error());
+        };
+        if type_path.qself.is_some() {
+            return Err(error());
+        }
+        let builder_type = match &options.builder {
+            Some(builder_type) => builder_type.clone(),
+            None => {
+                let mut builder_type = type_path.path.clone();
+                let last = builder_type.segments.last_mut().ok_or_else(error)?;
+                last.ident = format_ident!("{}Builder", last.ident.unraw());
+                builder_type
+            }
+        };
+        let mut error_type = builder_type.clone();
+        let last = error_type.segments.last_mut().ok_or_else(error)?;
+        last.ident = format_ident!("{}Error", last.ident);
+        last.arguments = PathArguments::None;
+
+        // tuple structの_0はCamelCaseにすると0になってしまうので、Fieldを付ける
+        let camel = to_camel_case(ident);
+        let variant = if camel.starts_with(|c: char| c.is_ascii_digit()) {
+            format_ident!("Field{}", camel)
+        } else {
+            format_ident!("{}", camel)
+        };
+
+        Ok(Self {
+            field: ident.unraw().to_string(),
+            builder_type,
+            error_type,
+            variant,
+            pattern: options.pattern,
+        })
+    }
+}
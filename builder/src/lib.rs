@@ -1,10 +1,22 @@
+use error::ErrorFactory;
+use field::{BuilderField, EachItem};
+use options::{BuilderPattern, StructOptions};
 use original::OriginalMethodsFactory;
-use proc_macro2::{Ident, Span};
-use quote::quote;
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
 use struct_builder::BuilderFactory;
-use syn::{parse_macro_input, DeriveInput, GenericArgument, PathArguments, Type};
+use syn::ext::IdentExt;
+use syn::{
+    parse_macro_input, Data, DataEnum, DataStruct, DeriveInput, Fields, GenericArgument,
+    GenericParam, Generics, PathArguments, PathSegment, Type, Visibility, WherePredicate,
+};
+use typestate::TypestateBuilderFactory;
+mod error;
+mod field;
+mod options;
 mod original;
 mod struct_builder;
+mod typestate;
 
 #[proc_macro_derive(Builder, attributes(builder))]
 pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -15,10 +27,33 @@ pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 }
 
 fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
-    let mut builder_factory = BuilderFactory::new(input.clone());
-    let original_method_factory = OriginalMethodsFactory::new(input.clone());
+    match &input.data {
+        Data::Enum(data_enum) => return expand_enum(&input, data_enum),
+        Data::Union(data_union) => {
+            return Err(syn::Error::new_spanned(
+                data_union.union_token,
+                "Builder cannot be derived for a union",
+            ))
+        }
+        Data::Struct(_) => {}
+    }
+
+    let options = StructOptions::from_attrs(&input.attrs)
+        .map_err(|error| with_field_errors(error, &input))?;
+    let target = BuildTarget::of_struct(&input);
+    let ExpandedBuilder {
+        vis,
+        builder_type,
+        filled_type,
+        tokens: builder_and_methods,
+        from_impl,
+    } = expand_builder(&input, options, target)?;
 
-    let builder_and_methods = builder_factory.build(&input)?;
+    let original_method_factory = OriginalMethodsFactory::new(
+        input,
+        vec![(vis.clone(), format_ident!("builder"), builder_type)],
+        Some((vis, filled_type)),
+    );
     let original_methods = original_method_factory.build();
 
     let expand = quote! {
@@ -27,98 +62,430 @@ fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
 
         #builder_and_methods
 
+        #from_impl
+
     };
     Ok(expand)
 }
 
-// literally Option< > only
-fn is_option(ty: &Type) -> bool {
-    let syn::Type::Path(type_path) = ty else {
-        return false;
-    };
-    // get_identは : Stringとかの時しかつかえない
-    let is_qself_none = type_path.qself.is_none();
+// struct-likeなvariantごとに、そのフィールドを持つstruct (MyEnumVariantA) があるものとしてbuilderを作る
+fn expand_enum(input: &DeriveInput, data_enum: &DataEnum) -> syn::Result<TokenStream> {
+    let mut errors = Errors::default();
+    let enum_options = errors
+        .ok(StructOptions::from_attrs(&input.attrs))
+        .unwrap_or_default();
+    // 全てのvariantのbuilderが同じ名前になってしまうので、nameはvariantごとに付けてもらう
+    if let Some(name) = &enum_options.name {
+        errors.push(syn::Error::new_spanned(
+            name,
+            "`builder(name = \"...\")` on an enum would give every variant's builder the same name, put it on each variant instead",
+        ));
+    }
+    let mut constructors = vec![];
+    let mut builders = vec![];
+    for variant in &data_enum.variants {
+        // tuple variantやunit variantにはbuilderを作らない
+        if !matches!(variant.fields, Fields::Named(_)) {
+            continue;
+        }
+        // enumに付いた#[builder(...)]を全てのvariantに効かせ、variantに付いたものを後から足す
+        let attrs = input
+            .attrs
+            .iter()
+            .chain(&variant.attrs)
+            .filter(|attr| attr.path().is_ident("builder"))
+            .cloned()
+            .collect::<Vec<_>>();
+        let variant_input = DeriveInput {
+            attrs,
+            vis: input.vis.clone(),
+            ident: format_ident!("{}{}", input.ident, variant.ident.unraw()),
+            generics: input.generics.clone(),
+            data: Data::Struct(DataStruct {
+                struct_token: Default::default(),
+                fields: variant.fields.clone(),
+                semi_token: None,
+            }),
+        };
+        let mut options = enum_options.clone();
+        if let Err(error) = options.parse_attrs(&variant.attrs) {
+            errors.push(with_field_errors(error, &variant_input));
+            continue;
+        }
+        if options.default {
+            errors.push(syn::Error::new_spanned(
+                &variant.ident,
+                "`builder(default)` needs a Default implementation of a struct, so it cannot be used on an enum",
+            ));
+            continue;
+        }
+        let target = BuildTarget::of_variant(input, &variant.ident);
+        // enumの値は他のvariantかもしれないので、From<MyEnum>やto_builder()は作らない
+        let Some(expanded) = errors.ok(expand_builder(&variant_input, options, target)) else {
+            continue;
+        };
+        constructors.push((
+            expanded.vis,
+            to_snake_case(&variant.ident),
+            expanded.builder_type,
+        ));
+        builders.push(expanded.tokens);
+    }
+    errors.finish()?;
+
+    let original_method_factory = OriginalMethodsFactory::new(input.clone(), constructors, None);
+    let original_methods = original_method_factory.build();
 
-    let is_leading_colon_none = type_path.path.leading_colon.is_none();
+    Ok(quote! {
+        #original_methods
+
+        #(#builders)*
+    })
+}
 
-    let is_option = type_path.path.segments.first().is_some_and(|segment| {
-        let is_option = segment.ident == "Option";
-        if let PathArguments::AngleBracketed(args) = &segment.arguments {
-            args.args.len() == 1 && is_option
-        } else {
-            false
+// #[builder(...)]が読めなかった時も、フィールドの間違いは一緒に報告する
+fn with_field_errors(mut error: syn::Error, input: &DeriveInput) -> syn::Error {
+    if let Err(field_error) = BuilderField::from_input(input, &StructOptions::default()) {
+        error.combine(field_error);
+    }
+    error
+}
+
+// expand_builderが作ったもの
+struct ExpandedBuilder {
+    // builderのvisibility。Foo::builder()などにも同じものを付ける
+    vis: Visibility,
+    // Foo::builder()が返す型
+    builder_type: TokenStream,
+    // 全てのフィールドがセットされたbuilderの型。From<Foo>とto_builder()はこれを返す
+    filled_type: TokenStream,
+    // builder・setter・build()など
+    tokens: TokenStream,
+    from_impl: TokenStream,
+}
+
+fn expand_builder(
+    input: &DeriveInput,
+    options: StructOptions,
+    target: BuildTarget,
+) -> syn::Result<ExpandedBuilder> {
+    if options.typestate {
+        let mut errors = Errors::default();
+        if let Some((pattern, span)) = options
+            .pattern
+            .filter(|(pattern, _)| *pattern != BuilderPattern::Owned)
+        {
+            let pattern = match pattern {
+                BuilderPattern::Mutable => "mutable",
+                _ => "immutable",
+            };
+            errors.push(syn::Error::new(
+                span,
+                format!(
+                    "a typestate builder is always owned, so it cannot use the {pattern} pattern"
+                ),
+            ));
+        }
+        if let Some(error) = &options.error {
+            errors.push(syn::Error::new_spanned(
+                error,
+                "a typestate builder cannot fail to build, so it has no error type",
+            ));
+        }
+        if let Some(validate) = &options.validate {
+            errors.push(syn::Error::new_spanned(
+                validate,
+                "a typestate builder cannot fail to build, so it cannot validate",
+            ));
+        }
+        if let Some(span) = options.serde {
+            errors.push(syn::Error::new(
+                span,
+                "a typestate builder changes its type when a required field is set, so it cannot be deserialized",
+            ));
         }
-    });
+        if let Some(span) = options.env {
+            errors.push(syn::Error::new(
+                span,
+                "a typestate builder changes its type when a required field is set, so it cannot set fields from `env`",
+            ));
+        }
+        let vis = options.builder_vis(input);
+        let typestate_factory =
+            errors.finish_with(TypestateBuilderFactory::new(input.clone(), options, target))?;
+        Ok(ExpandedBuilder {
+            vis,
+            builder_type: typestate_factory.builder_type(),
+            filled_type: typestate_factory.filled_type(),
+            tokens: typestate_factory.build(),
+            from_impl: typestate_factory.impl_from(),
+        })
+    } else {
+        let fields = BuilderField::from_input(input, &options)?;
+        let sub_builders = fields
+            .iter()
+            .filter_map(|field| field.sub_builder.clone())
+            .collect();
+        let vis = options.builder_vis(input);
+        let error = ErrorFactory::new(input, &options, sub_builders).build();
+        let mut builder_factory = BuilderFactory::new(input.clone(), options, target, fields);
+        let builder_type = builder_factory.builder_type();
+        let from_impl = builder_factory.impl_from(input);
+        let builder_and_methods = builder_factory.build(input);
+        Ok(ExpandedBuilder {
+            vis,
+            filled_type: builder_type.clone(),
+            builder_type,
+            tokens: quote! {
+                #builder_and_methods
 
-    is_qself_none && is_leading_colon_none && is_option
+                #error
+            },
+            from_impl,
+        })
+    }
 }
 
-fn is_vector(ty: &Type) -> bool {
-    let syn::Type::Path(type_path) = ty else {
-        return false;
-    };
-    // get_identは : Stringとかの時しかつかえない
-    let is_qself_none = type_path.qself.is_none();
+// build()が返す型と、その値を作る時のpath
+// structならどちらもFoo、enumならMyEnumとMyEnum::VariantAになる
+struct BuildTarget {
+    ty: TokenStream,
+    path: TokenStream,
+    // variantのフィールドはenumのジェネリクスを全部使うとは限らないので、
+    // variantのbuilderは全てのジェネリクスを使ったことにするPhantomDataを持つ
+    marker: Option<TokenStream>,
+}
+
+impl BuildTarget {
+    fn of_struct(input: &DeriveInput) -> Self {
+        let ident = &input.ident;
+        let (_, ty_generics, _) = input.generics.split_for_impl();
+        Self {
+            ty: quote!(#ident #ty_generics),
+            path: quote!(#ident),
+            marker: None,
+        }
+    }
 
-    let is_leading_colon_none = type_path.path.leading_colon.is_none();
+    fn of_variant(input: &DeriveInput, variant: &Ident) -> Self {
+        let ident = &input.ident;
+        let (_, ty_generics, _) = input.generics.split_for_impl();
+        Self {
+            ty: quote!(#ident #ty_generics),
+            path: quote!(#ident::#variant),
+            marker: Self::marker(&input.generics),
+        }
+    }
 
-    let is_vector = type_path.path.segments.first().is_some_and(|segment| {
-        let is_vector = segment.ident == "Vec";
-        if let PathArguments::AngleBracketed(args) = &segment.arguments {
-            args.args.len() == 1 && is_vector
-        } else {
-            false
+    // fn() -> (&'a (), T, [(); N])。fn()にしておけば、Tが何であってもSendやSyncなどに影響しない
+    fn marker(generics: &Generics) -> Option<TokenStream> {
+        if generics.params.is_empty() {
+            return None;
         }
-    });
+        let params = generics.params.iter().map(|param| match param {
+            GenericParam::Lifetime(lifetime) => {
+                let lifetime = &lifetime.lifetime;
+                quote!(&#lifetime ())
+            }
+            GenericParam::Type(ty) => {
+                let ident = &ty.ident;
+                quote!(#ident)
+            }
+            GenericParam::Const(constant) => {
+                let ident = &constant.ident;
+                quote!([(); #ident])
+            }
+        });
+        Some(quote!(fn() -> (#(#params,)*)))
+    }
 
-    is_qself_none && is_leading_colon_none && is_vector
+    // ドキュメントから作るものへのリンク。[`MyEnum::Variant`]
+    fn doc_link(&self) -> String {
+        format!("[`{}`]", self.path.to_string().replace(' ', ""))
+    }
 }
 
-// literally Option< > only
-// if not it's option, return original type
-fn extract_type_from_option(ty: &Type) -> Type {
-    let syn::Type::Path(type_path) = ty else {
-        panic!("expect Option<T>")
-    };
+// 見つかったエラーを1つずつ返さず、まとめて報告するためのもの
+#[derive(Default)]
+struct Errors(Option<syn::Error>);
 
-    let segment = type_path.path.segments.first().expect("Option<T>");
+impl Errors {
+    fn push(&mut self, error: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
 
-    let PathArguments::AngleBracketed(args) = &segment.arguments else {
-        panic!("expect Option<T>")
-    };
+    // Errなら覚えておいてNoneを返す
+    fn ok<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
 
-    assert!(args.args.len() == 1, "expect Option<T>");
+    fn finish(self) -> syn::Result<()> {
+        self.finish_with(Ok(()))
+    }
 
-    let GenericArgument::Type(ty) = args.args.first().unwrap() else {
-        panic!("expect Option<T>")
-    };
+    // resultのエラーも足して、1つでもあれば全部まとめて返す
+    fn finish_with<T>(self, result: syn::Result<T>) -> syn::Result<T> {
+        match (self.0, result) {
+            (None, result) => result,
+            (Some(mut errors), Err(error)) => {
+                errors.combine(error);
+                Err(errors)
+            }
+            (Some(errors), Ok(_)) => Err(errors),
+        }
+    }
+}
 
-    ty.clone()
+// Option<T>, std::option::Option<T>, ::core::option::Option<T>
+// cloneが要るメソッドに付ける`T: Clone`境界
+// ただの`T: Clone`だとCloneでない型でderive自体が失敗するので (rust#48214)、
+// for<'__builder>を付けて、Cloneでない時はそのメソッドが使えないだけにする
+fn deferred_clone_bound(ty: impl ToTokens) -> WherePredicate {
+    syn::parse_quote!(for<'__builder> #ty: ::core::clone::Clone)
 }
 
-fn extract_type_from_vector(ty: &Type) -> Type {
-    let syn::Type::Path(type_path) = ty else {
-        panic!("expect Vec<T>")
-    };
+fn is_option(ty: &Type) -> bool {
+    option_inner(ty).is_some()
+}
 
-    let segment = type_path.path.segments.first().expect("Vec<T>");
+// 標準ライブラリの型のsegment (Option<T>など)
+// 名前だけか、std::option::Optionのようにcrateとmoduleから書かれたものだけを認める
+// crate::Optionやmy::Optionのように別のmoduleから来た型は、同じ名前でも違うものとして扱う
+fn std_type<'a>(
+    ty: &'a Type,
+    crates: &[&str],
+    module: &str,
+    name: &str,
+) -> Option<&'a PathSegment> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    if type_path.qself.is_some() {
+        return None;
+    }
+    let segments = type_path.path.segments.iter().collect::<Vec<_>>();
+    let (last, prefix) = segments.split_last()?;
+    let is_std = match prefix {
+        [] => type_path.path.leading_colon.is_none(),
+        [krate, path] => {
+            crates.iter().any(|std_crate| krate.ident == std_crate)
+                && krate.arguments.is_none()
+                && path.ident == module
+                && path.arguments.is_none()
+        }
+        _ => false,
+    };
+    (is_std && last.ident == name).then_some(*last)
+}
 
+// eachで1つずつ足せるコレクションなら、その要素の型
+// std::collections::HashMapのように書かれていても分かるよう、最後のsegmentで判断する
+fn collection_item(ty: &Type) -> Option<EachItem> {
+    let syn::Type::Path(type_path) = ty else {
+        return None;
+    };
+    if type_path.qself.is_some() {
+        return None;
+    }
+    let segment = type_path.path.segments.last()?;
     let PathArguments::AngleBracketed(args) = &segment.arguments else {
-        panic!("expect Vec<T>")
+        return None;
     };
+    let types = args
+        .args
+        .iter()
+        .filter_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
 
-    assert!(args.args.len() == 1, "expect Vec<T>");
+    match (segment.ident.to_string().as_str(), types.as_slice()) {
+        ("Vec" | "VecDeque" | "LinkedList" | "BinaryHeap" | "BTreeSet", [item]) => {
+            Some(EachItem::Single(item.clone()))
+        }
+        // HashSet<T, S>, HashMap<K, V, S>のようにhasherを指定していても良い
+        ("HashSet", [item] | [item, _]) => Some(EachItem::Single(item.clone())),
+        ("BTreeMap", [key, value]) | ("HashMap", [key, value] | [key, value, _]) => {
+            Some(EachItem::Pair(key.clone(), value.clone()))
+        }
+        _ => None,
+    }
+}
 
-    let GenericArgument::Type(ty) = args.args.first().unwrap() else {
-        panic!("expect Vec<T>")
+// Option<T>のT。Option<T>でなければNone
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let segment = std_type(ty, &["std", "core"], "option", "Option")?;
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
     };
+    match args.args.iter().collect::<Vec<_>>().as_slice() {
+        [GenericArgument::Type(ty)] => Some(ty),
+        _ => None,
+    }
+}
 
-    ty.clone()
+// ジェネリクスを型引数として並べたもの (<'a, T, N>の中身)
+fn generic_args(generics: &Generics) -> Vec<TokenStream> {
+    generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(lifetime) => {
+                let lifetime = &lifetime.lifetime;
+                quote!(#lifetime)
+            }
+            GenericParam::Type(ty) => {
+                let ident = &ty.ident;
+                quote!(#ident)
+            }
+            GenericParam::Const(constant) => {
+                let ident = &constant.ident;
+                quote!(#ident)
+            }
+        })
+        .collect()
 }
 
-fn builder_name(original_input: &DeriveInput) -> Ident {
-    Ident::new(
-        &format!("{}Builder", &original_input.ident),
-        Span::call_site(),
-    )
+// current_dir -> CurrentDir
+fn to_camel_case(ident: &Ident) -> String {
+    ident
+        .unraw()
+        .to_string()
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_uppercase().chain(chars).collect::<String>()
+            })
+        })
+        .collect()
+}
+
+// VariantA -> variant_a, HTTPRequest -> http_request, Type -> r#type
+// 大文字が続く所は1つの単語として扱い、次の単語の頭文字の前で区切る
+fn to_snake_case(ident: &Ident) -> Ident {
+    let chars = ident.unraw().to_string().chars().collect::<Vec<_>>();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i != 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if !prev.is_uppercase() && prev != '_' || prev.is_uppercase() && next_is_lower {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    // Move -> r#move。selfなどraw identifierにできないものは後ろに_を付ける
+    if syn::parse_str::<Ident>(&snake).is_ok() {
+        format_ident!("{}", snake)
+    } else if matches!(snake.as_str(), "crate" | "self" | "super") {
+        format_ident!("{}_", snake)
+    } else {
+        Ident::new_raw(&snake, Span::call_site())
+    }
 }
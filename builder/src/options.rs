@@ -0,0 +1,640 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+use syn::{
+    parse_quote, Attribute, DeriveInput, Expr, Field, Ident, LitBool, LitStr, Member, Path, Result,
+    Token, Type, Visibility,
+};
+
+use crate::Errors;
+
+// struct自体に付いている#[builder(...)]
+#[derive(Default, Clone)]
+pub struct StructOptions {
+    pub typestate: bool,
+    // #[builder(error = "path::to::Error")]
+    pub error: Option<Path>,
+    // #[builder(default)] 未設定のフィールドはstructのDefault実装から持ってくる
+    pub default: bool,
+    // #[builder(setter(into, prefix = "with_"))] 全てのsetterに効く
+    pub setter_into: bool,
+    pub setter_prefix: Option<String>,
+    // #[builder(pattern = "owned")] spanはエラーの報告に使う
+    pub pattern: Option<(BuilderPattern, Span)>,
+    // #[builder(derive(Debug, PartialEq))] builderに付けるderive
+    pub derives: Vec<Path>,
+    // #[builder(validate = "path::to::fn")] build()の最後に&Fooを渡して呼ぶ
+    pub validate: Option<Path>,
+    // #[builder(name = "FooFactory")] 無ければFooBuilder
+    pub name: Option<Ident>,
+    // #[builder(vis = "pub(crate)")] 無ければ元のstructと同じ
+    pub vis: Option<Visibility>,
+    // #[builder(no_std)] VecやStringを::allocから使い、std::error::Errorはfeature = "std"の時だけ実装する
+    pub no_std: bool,
+    // #[builder(env)] apply_kv()とfrom_env()を作る。spanはエラーの報告に使う
+    pub env: Option<Span>,
+    // #[builder(serde)] builderにDeserializeをderiveする。spanはエラーの報告に使う
+    pub serde: Option<Span>,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum BuilderPattern {
+    // setterは&mut selfを受け取って&mut Selfを返す
+    #[default]
+    Mutable,
+    // setterはselfを受け取ってSelfを返す。build()はフィールドをmoveする
+    Owned,
+    // setterは&selfから新しいbuilderを作る
+    Immutable,
+}
+
+impl BuilderPattern {
+    fn from_lit(pattern: &LitStr) -> Result<Self> {
+        match pattern.value().as_str() {
+            "mutable" => Ok(Self::Mutable),
+            "owned" => Ok(Self::Owned),
+            "immutable" => Ok(Self::Immutable),
+            _ => Err(syn::Error::new_spanned(
+                pattern,
+                r#"expected "mutable", "owned" or "immutable""#,
+            )),
+        }
+    }
+}
+
+impl StructOptions {
+    pub fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Self::default();
+        options.parse_attrs(attrs)?;
+        Ok(options)
+    }
+
+    // 読んだものでoptionsを上書きする。enumならvariantに付いたものを、enumに付いたものの後から読む
+    pub fn parse_attrs(&mut self, attrs: &[Attribute]) -> Result<()> {
+        let mut keys = Keys::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("builder")) {
+            let result = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("setter") {
+                    return meta.parse_nested_meta(|meta| {
+                        keys.insert("setter", &meta.path);
+                        if meta.path.is_ident("into") {
+                            self.setter_into = true;
+                            Ok(())
+                        } else if meta.path.is_ident("prefix") {
+                            let prefix: LitStr = meta.value()?.parse()?;
+                            self.setter_prefix = Some(prefix.value());
+                            Ok(())
+                        } else {
+                            Err(meta
+                                .error(r#"expected `setter(into)` or `setter(prefix = "...")`"#))
+                        }
+                    });
+                } else if meta.path.is_ident("derive") {
+                    return meta.parse_nested_meta(|meta| {
+                        keys.insert("derive", &meta.path);
+                        self.derives.push(meta.path);
+                        Ok(())
+                    });
+                }
+                keys.insert("builder", &meta.path);
+                if meta.path.is_ident("typestate") {
+                    self.typestate = true;
+                    Ok(())
+                } else if meta.path.is_ident("error") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    self.error = Some(path.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    self.default = true;
+                    Ok(())
+                } else if meta.path.is_ident("no_std") {
+                    self.no_std = true;
+                    Ok(())
+                } else if meta.path.is_ident("env") {
+                    self.env = Some(meta.path.span());
+                    Ok(())
+                } else if meta.path.is_ident("serde") {
+                    // 生成したコードは利用者のcrateのserdeを使うので、featureは使ってよいかの確認だけ
+                    if !cfg!(feature = "serde") {
+                        return Err(meta.error(
+                            r#"`builder(serde)` needs the "serde" feature of derive_builder"#,
+                        ));
+                    }
+                    self.serde = Some(meta.path.span());
+                    Ok(())
+                } else if meta.path.is_ident("pattern") {
+                    let pattern: LitStr = meta.value()?.parse()?;
+                    self.pattern = Some((BuilderPattern::from_lit(&pattern)?, pattern.span()));
+                    Ok(())
+                } else if meta.path.is_ident("validate") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    self.validate = Some(path.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("name") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    self.name = Some(name.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("vis") {
+                    let vis: LitStr = meta.value()?.parse()?;
+                    self.vis = Some(vis.parse()?);
+                    Ok(())
+                } else {
+                    Err(unknown_key(&meta, STRUCT_KEYS))
+                }
+            });
+            keys.errors.ok(result);
+        }
+        keys.errors.finish()
+    }
+}
+
+// 知らないkeyの時に、綴りの近いものを教えるためのkeyの一覧
+const STRUCT_KEYS: &[&str] = &[
+    "typestate",
+    "error",
+    "default",
+    "no_std",
+    "env",
+    "serde",
+    "pattern",
+    "validate",
+    "name",
+    "vis",
+    "setter",
+    "derive",
+];
+const FIELD_KEYS: &[&str] = &[
+    "each",
+    "default",
+    "validate",
+    "sub_builder",
+    "env",
+    "optional",
+    "required",
+    "merge",
+    "setter",
+];
+
+// unknown `builder` attribute `eac`
+// help: did you mean `each`?
+fn unknown_key(meta: &ParseNestedMeta, known: &[&str]) -> syn::Error {
+    let path = &meta.path;
+    let key = quote!(#path).to_string();
+    let message = format!("unknown `builder` attribute `{key}`");
+    // 1文字違いや1文字足りないくらいの打ち間違いだけ拾う
+    let similar = known
+        .iter()
+        .map(|candidate| (edit_distance(&key, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= 1.max(candidate.len() / 3))
+        .min_by_key(|(distance, _)| *distance);
+    match similar {
+        Some((_, candidate)) => meta.error(format!("{message}\nhelp: did you mean `{candidate}`?")),
+        None => meta.error(message),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+// 1つの#[builder(...)]の並びで出てきたkey。同じkeyが2回あったり、一緒に使えないkeyがあればエラーにする
+// エラーはその場で返さず、他の間違いと一緒にまとめて報告する
+#[derive(Default)]
+struct Keys {
+    // builder(default)やsetter(into)と、それが書かれた場所
+    seen: Vec<(String, Span)>,
+    errors: Errors,
+}
+
+impl Keys {
+    // builder(default)なら、parentは"builder"でpathはdefault
+    fn insert(&mut self, parent: &str, path: &Path) {
+        let key = format!("{parent}({})", quote!(#path));
+        if self.get(&key).is_some() {
+            self.errors
+                .push(syn::Error::new_spanned(path, format!("duplicate `{key}`")));
+        } else {
+            self.seen.push((key, path.span()));
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Span> {
+        self.seen
+            .iter()
+            .find_map(|(seen, span)| (seen == key).then_some(*span))
+    }
+
+    // 両方書かれていたら、後から書かれた方を指してエラーにする
+    fn conflict(&mut self, first: &str, second: &str) {
+        let position = |key: &str| self.seen.iter().position(|(seen, _)| seen == key);
+        let (Some(first), Some(second)) = (position(first), position(second)) else {
+            return;
+        };
+        let (first, second) = (first.min(second), first.max(second));
+        let (first, _) = &self.seen[first];
+        let (second, span) = &self.seen[second];
+        self.errors.push(syn::Error::new(
+            *span,
+            format!("`{first}` and `{second}` cannot be used together"),
+        ));
+    }
+}
+
+// フィールドに付いている#[builder(...)]
+#[derive(Default)]
+pub struct FieldOptions {
+    // #[builder(each = "arg")] / #[builder(each(name = "arg", item = "Type"))]
+    pub each: Option<EachOptions>,
+    pub default: Option<FieldDefault>,
+    pub setter: SetterOptions,
+    // #[builder(validate = "fn")] build()の時にフィールドの値の参照を渡して呼ぶ
+    pub validate: Option<Path>,
+    // #[builder(sub_builder)] フィールドの型のbuilderを持ち、build()の時に一緒にbuildする
+    pub sub_builder: Option<SubBuilderOptions>,
+    // #[builder(merge = "append")] eachのコレクションをmerge()でどうするか
+    pub merge: Option<MergeStrategy>,
+    // #[builder(env = "NAME")] / #[builder(env(skip))] apply_kv()でのkey
+    pub env: Option<FieldEnv>,
+    // #[builder(optional)]ならSome(true)、#[builder(required)]ならSome(false)
+    // 型からOptionかどうか分からない時 (type aliasなど) に使う
+    pub optional: Option<bool>,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    // 後から来たbuilderの要素を足す
+    #[default]
+    Append,
+    // 後から来たbuilderにセットされていれば、まるごと置き換える
+    Replace,
+}
+
+// #[builder(sub_builder)] / #[builder(sub_builder(pattern = "owned", builder = "BarFactory"))]
+pub struct SubBuilderOptions {
+    // 中のbuilderのpattern。外側のクロージャのsetterが、中のsetterの受け取り方に合わせる
+    pub pattern: BuilderPattern,
+    // 中のbuilderの型。builder(name = "...")で名前を変えた時に使う。無ければBarBuilder
+    pub builder: Option<Path>,
+}
+
+pub struct EachOptions {
+    pub name: Ident,
+    // 要素の型。無ければフィールドの型から推測する
+    pub item: Option<Type>,
+}
+
+impl EachOptions {
+    fn parse(meta: &ParseNestedMeta) -> Result<Self> {
+        if meta.input.peek(Token![=]) {
+            let name: LitStr = meta.value()?.parse()?;
+            return Ok(Self {
+                name: name.parse()?,
+                item: None,
+            });
+        }
+        let mut name = None;
+        let mut item = None;
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                let value: LitStr = meta.value()?.parse()?;
+                name = Some(value.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("item") {
+                let value: LitStr = meta.value()?.parse()?;
+                item = Some(value.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error(r#"expected `each(name = "...")` or `each(item = "...")`"#))
+            }
+        })?;
+        let name = name.ok_or_else(|| meta.error(r#"`each(...)` needs `name = "..."`"#))?;
+        Ok(Self { name, item })
+    }
+}
+
+impl SubBuilderOptions {
+    fn parse(meta: &ParseNestedMeta) -> Result<Self> {
+        let mut options = Self {
+            pattern: BuilderPattern::default(),
+            builder: None,
+        };
+        if !meta.input.peek(syn::token::Paren) {
+            return Ok(options);
+        }
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("pattern") {
+                let pattern: LitStr = meta.value()?.parse()?;
+                options.pattern = BuilderPattern::from_lit(&pattern)?;
+                Ok(())
+            } else if meta.path.is_ident("builder") {
+                let builder: LitStr = meta.value()?.parse()?;
+                options.builder = Some(builder.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error(
+                    r#"expected `sub_builder(pattern = "...")` or `sub_builder(builder = "...")`"#,
+                ))
+            }
+        })?;
+        Ok(options)
+    }
+}
+
+impl FieldEnv {
+    fn parse(meta: &ParseNestedMeta) -> Result<Self> {
+        if meta.input.peek(Token![=]) {
+            let name: LitStr = meta.value()?.parse()?;
+            return Ok(Self::Name(name.value()));
+        }
+        let mut skip = false;
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error(r#"expected `env = "..."` or `env(skip)`"#))
+            }
+        })?;
+        if !skip {
+            return Err(meta.error(r#"expected `env = "..."` or `env(skip)`"#));
+        }
+        Ok(Self::Skip)
+    }
+}
+
+// #[builder(setter(...))]
+pub struct SetterOptions {
+    // 引数をimpl Into<T>で受け取る
+    pub into: bool,
+    // TryIntoで変換するtry_<field>()も作る
+    pub try_into: bool,
+    // falseならOption<T>のフィールドのsetterがOption<T>をそのまま受け取る
+    pub strip_option: bool,
+    pub name: Option<Ident>,
+    // setterを作らない。defaultが必要
+    pub skip: bool,
+    // setter(vis = "pub(crate)") 無ければpub
+    pub vis: Option<Visibility>,
+}
+
+impl Default for SetterOptions {
+    fn default() -> Self {
+        Self {
+            into: false,
+            try_into: false,
+            strip_option: true,
+            name: None,
+            skip: false,
+            vis: None,
+        }
+    }
+}
+
+pub enum FieldEnv {
+    // 無ければフィールド名を大文字にしたもの
+    Name(String),
+    // apply_kv()で扱わない
+    Skip,
+}
+
+pub enum FieldDefault {
+    // #[builder(default)]
+    Trait,
+    // #[builder(default = "expr")] 未設定の時だけ評価される。元の文字列はドキュメントに使う
+    Expr(Expr, String),
+}
+
+impl FieldOptions {
+    pub fn from_field(field: &Field) -> Result<Self> {
+        let mut options = Self::default();
+        let mut keys = Keys::default();
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("builder"))
+        {
+            let result = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("setter") {
+                    return options.setter.parse(&meta, &mut keys);
+                }
+                keys.insert("builder", &meta.path);
+                if meta.path.is_ident("each") {
+                    options.each = Some(EachOptions::parse(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    options.default = if meta.input.peek(Token![=]) {
+                        let expr: LitStr = meta.value()?.parse()?;
+                        Some(FieldDefault::Expr(expr.parse()?, expr.value()))
+                    } else {
+                        Some(FieldDefault::Trait)
+                    };
+                    Ok(())
+                } else if meta.path.is_ident("validate") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    options.validate = Some(path.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("sub_builder") {
+                    options.sub_builder = Some(SubBuilderOptions::parse(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("env") {
+                    options.env = Some(FieldEnv::parse(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("optional") {
+                    options.optional = Some(true);
+                    Ok(())
+                } else if meta.path.is_ident("required") {
+                    options.optional = Some(false);
+                    Ok(())
+                } else if meta.path.is_ident("merge") {
+                    let merge: LitStr = meta.value()?.parse()?;
+                    options.merge = Some(match merge.value().as_str() {
+                        "append" => MergeStrategy::Append,
+                        "replace" => MergeStrategy::Replace,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                merge,
+                                r#"expected "append" or "replace""#,
+                            ))
+                        }
+                    });
+                    Ok(())
+                } else {
+                    Err(unknown_key(&meta, FIELD_KEYS))
+                }
+            });
+            keys.errors.ok(result);
+        }
+
+        keys.conflict("builder(optional)", "builder(required)");
+        // 未設定の時の値が決まっているので、必須にはできない
+        for other in ["builder(default)", "builder(each)", "builder(sub_builder)"] {
+            keys.conflict("builder(required)", other);
+        }
+        // sub_builderのsetterは中のbuilderを受け取るので、値のための指定とは一緒に使えない
+        for other in [
+            "builder(each)",
+            "builder(default)",
+            "setter(skip)",
+            "setter(into)",
+            "setter(try_into)",
+            "setter(strip_option)",
+        ] {
+            keys.conflict("builder(sub_builder)", other);
+        }
+        // setterを作らないので、setterのための指定は意味がない
+        for other in [
+            "builder(each)",
+            "setter(into)",
+            "setter(try_into)",
+            "setter(strip_option)",
+            "setter(name)",
+            "setter(vis)",
+        ] {
+            keys.conflict("setter(skip)", other);
+        }
+        // eachやsub_builderのフィールドは、1つの文字列から値を作れないのでapply_kv()で扱わない
+        if let Some(FieldEnv::Name(_)) = options.env {
+            for other in ["builder(each)", "builder(sub_builder)", "setter(skip)"] {
+                keys.conflict("builder(env)", other);
+            }
+        }
+        if let (Some(span), None) = (keys.get("builder(merge)"), keys.get("builder(each)")) {
+            keys.errors.push(syn::Error::new(
+                span,
+                "`merge` only applies to a collection with `each`",
+            ));
+        }
+
+        keys.errors.finish()?;
+        Ok(options)
+    }
+}
+
+impl SetterOptions {
+    fn parse(&mut self, meta: &ParseNestedMeta, keys: &mut Keys) -> Result<()> {
+        meta.parse_nested_meta(|meta| {
+            keys.insert("setter", &meta.path);
+            if meta.path.is_ident("into") {
+                self.into = true;
+                Ok(())
+            } else if meta.path.is_ident("try_into") {
+                self.try_into = true;
+                Ok(())
+            } else if meta.path.is_ident("strip_option") {
+                self.strip_option = if meta.input.peek(Token![=]) {
+                    let strip_option: LitBool = meta.value()?.parse()?;
+                    strip_option.value
+                } else {
+                    true
+                };
+                Ok(())
+            } else if meta.path.is_ident("name") {
+                let name: LitStr = meta.value()?.parse()?;
+                self.name = Some(name.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                self.skip = true;
+                Ok(())
+            } else if meta.path.is_ident("vis") {
+                let vis: LitStr = meta.value()?.parse()?;
+                self.vis = Some(vis.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error(
+                    r#"expected `setter(into)`, `setter(try_into)`, `setter(strip_option = ...)`, `setter(name = "...")`, `setter(vis = "...")` or `setter(skip)`"#,
+                ))
+            }
+        })
+    }
+}
+
+impl FieldOptions {
+    // 未設定の時に使う値 (structのdefaultは`__default`に入っている前提)
+    pub fn default_value(&self, member: &Member, struct_default: bool) -> Option<TokenStream> {
+        match &self.default {
+            Some(FieldDefault::Trait) => Some(quote!(::core::default::Default::default())),
+            Some(FieldDefault::Expr(expr, _)) => Some(quote!(#expr)),
+            None if struct_default => Some(quote!(__default.#member)),
+            None => None,
+        }
+    }
+}
+
+impl StructOptions {
+    pub fn pattern(&self) -> BuilderPattern {
+        self.pattern.map(|(pattern, _)| pattern).unwrap_or_default()
+    }
+
+    pub fn builder_name(&self, original_input: &DeriveInput) -> Ident {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format_ident!("{}Builder", original_input.ident))
+    }
+
+    // エラー型はbuilderの名前から作る。FooBuilderならFooBuilderError
+    pub fn builder_error_name(&self, original_input: &DeriveInput) -> Ident {
+        format_ident!("{}Error", self.builder_name(original_input))
+    }
+
+    pub fn builder_vis(&self, original_input: &DeriveInput) -> Visibility {
+        self.vis
+            .clone()
+            .unwrap_or_else(|| original_input.vis.clone())
+    }
+
+    // coreに無いVecやStringなどを使う時のcrate
+    pub fn alloc_crate(&self) -> TokenStream {
+        if self.no_std {
+            quote!(::alloc)
+        } else {
+            quote!(::std)
+        }
+    }
+
+    // フィールドの型にCloneやDebugを要求しないよう、指定されたものだけderiveする
+    // immutable patternのsetterはbuilderをcloneするので、Cloneだけは必ず付ける
+    pub fn builder_derive_attributes(&self) -> Vec<Attribute> {
+        let mut derives = self
+            .derives
+            .iter()
+            .map(|path| quote!(#path))
+            .collect::<Vec<_>>();
+        let has_clone = self.derives.iter().any(|path| {
+            path.segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Clone")
+        });
+        if self.pattern() == BuilderPattern::Immutable && !has_clone {
+            derives.push(quote!(::core::clone::Clone));
+        }
+        // builderのフィールドはOptionなので、書かれていないフィールドは未設定のままになる
+        // sub_builderのフィールドは、serde(default)で空のbuilderにする
+        if self.serde.is_some() {
+            derives.push(quote!(::serde::Deserialize));
+        }
+        let mut attrs = Vec::new();
+        if !derives.is_empty() {
+            attrs.push(parse_quote!(#[derive(#(#derives),*)]));
+        }
+        if self.serde.is_some() {
+            attrs.push(parse_quote!(#[serde(default)]));
+        }
+        attrs
+    }
+}
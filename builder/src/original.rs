@@ -1,42 +1,76 @@
-use proc_macro2::TokenStream;
+use proc_macro2::{Ident, TokenStream};
 use quote::quote;
-use syn::DeriveInput;
+use syn::{DeriveInput, Visibility};
 
-use crate::builder_name;
+use crate::deferred_clone_bound;
 
 pub struct OriginalMethodsFactory {
     base: DeriveInput,
+    // builderを返すメソッドのvisibilityと名前、builderの型
+    // structならbuilder()だけ、enumならvariantごとにvariant_a()などを作る
+    constructors: Vec<(Visibility, Ident, TokenStream)>,
+    // to_builder()が返すbuilderの型。enumには作らない
+    to_builder: Option<(Visibility, TokenStream)>,
 }
 
 impl OriginalMethodsFactory {
-    pub fn new(input: DeriveInput) -> Self {
-        Self { base: input }
+    pub fn new(
+        input: DeriveInput,
+        constructors: Vec<(Visibility, Ident, TokenStream)>,
+        to_builder: Option<(Visibility, TokenStream)>,
+    ) -> Self {
+        Self {
+            base: input,
+            constructors,
+            to_builder,
+        }
     }
 
     pub fn impl_methods(&self, methods: Vec<TokenStream>) -> TokenStream {
         let ident = self.base.ident.clone();
+        let (impl_generics, ty_generics, where_clause) = self.base.generics.split_for_impl();
         quote! {
-          impl #ident {
+          impl #impl_generics #ident #ty_generics #where_clause {
             #(#methods)*
           }
         }
     }
 
     pub fn build(&self) -> TokenStream {
-        let builder_method = self.builder_method();
-
-        let methods = vec![builder_method];
+        let methods = self
+            .constructors
+            .iter()
+            .map(|(vis, name, builder_type)| Self::builder_method(vis, name, builder_type))
+            .chain(
+                self.to_builder
+                    .as_ref()
+                    .map(|(vis, builder_type)| Self::to_builder_method(vis, builder_type)),
+            )
+            .collect();
 
         self.impl_methods(methods)
         // quote! {}
     }
 
-    fn builder_method(&self) -> TokenStream {
-        let builder_name = builder_name(&self.base);
+    fn builder_method(vis: &Visibility, name: &Ident, builder_type: &TokenStream) -> TokenStream {
+        quote! {
+          /// Creates a builder with no fields set.
+          #vis fn #name() -> #builder_type {
+            <#builder_type as ::core::default::Default>::default()
+          }
+        }
+    }
 
+    // From<Foo>はフィールドをmoveするので、ここではselfをcloneしてから渡す
+    fn to_builder_method(vis: &Visibility, builder_type: &TokenStream) -> TokenStream {
+        let clone_bound = deferred_clone_bound(quote!(Self));
         quote! {
-          pub fn builder() -> #builder_name {
-            #builder_name::default()
+          /// Creates a builder with every field set from a clone of this value.
+          #vis fn to_builder(&self) -> #builder_type
+          where
+              #clone_bound,
+          {
+            <#builder_type as ::core::convert::From<Self>>::from(::core::clone::Clone::clone(self))
           }
         }
     }
@@ -1,263 +1,802 @@
-use proc_macro2::Ident;
-use proc_macro2::{Span, TokenStream};
-use quote::quote;
-use syn::Result;
-use syn::{parse_quote, token::Pub, Attribute, Data, DeriveInput, Expr, Field, Lit, Visibility};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{parse_quote, parse_quote_spanned, Data, DataStruct, DeriveInput, Fields, Visibility};
 
-use crate::{
-    builder_name, extract_type_from_option, extract_type_from_vector, is_option, is_vector,
-};
+use crate::field::BuilderField;
+use crate::options::{BuilderPattern, StructOptions};
+use crate::{deferred_clone_bound, BuildTarget};
 
 pub struct BuilderFactory {
     base: DeriveInput,
+    options: StructOptions,
+    target: BuildTarget,
+    // 元のstructのフィールド。attributeは一度だけ読んで、ここから全てのメソッドを作る
+    fields: Vec<BuilderField>,
 }
 
 impl BuilderFactory {
-    pub fn new(original: DeriveInput) -> Self {
-        Self { base: original }
+    pub fn new(
+        original: DeriveInput,
+        options: StructOptions,
+        target: BuildTarget,
+        fields: Vec<BuilderField>,
+    ) -> Self {
+        Self {
+            base: original,
+            options,
+            target,
+            fields,
+        }
     }
 
-    fn set_builder_name(&mut self, original_input: &DeriveInput) {
-        self.base.ident = builder_name(original_input);
+    pub fn builder_type(&self) -> TokenStream {
+        let builder_name = self.options.builder_name(&self.base);
+        let (_, ty_generics, _) = self.base.generics.split_for_impl();
+        quote! {
+            #builder_name #ty_generics
+        }
     }
 
-    fn convert_fields_into_builder(&mut self) -> Result<()> {
-        let Data::Struct(data_struct) = &mut self.base.data else {
-            return Err(syn::Error::new(Span::call_site(), "Should be Struct"));
-        };
-        let syn::Fields::Named(fields_named) = &mut data_struct.fields else {
-            return Err(syn::Error::new(
-                Span::call_site(),
-                "field name is necessary",
-            ));
-        };
+    fn set_builder_name(&mut self, original_input: &DeriveInput) {
+        self.base.ident = self.options.builder_name(original_input);
+        self.base.vis = self.options.builder_vis(original_input);
+    }
 
-        fields_named.named.iter_mut().for_each(|field| {
-            let original_type = field.ty.clone();
-            if !(is_option(&original_type)
-                || is_vector(&original_type) && field.attrs.iter().any(Self::is_attribute_builder))
-            {
-                field.ty = parse_quote! {
-                    std::option::Option<#original_type>
-                };
-            }
-            field.vis = Visibility::Public(Pub::default());
-            field.attrs = vec![]
+    fn convert_fields_into_builder(&mut self) {
+        // setter(skip)のフィールドはbuilderに持たない
+        // tuple structでも、builderは_0, _1...という名前のフィールドを持つ
+        // 中身の持ち方は外から見えないようにして、get_<field>()などから触ってもらう
+        let fields = self
+            .fields
+            .iter()
+            .filter(|builder_field| !builder_field.is_skipped())
+            .map(|builder_field| {
+                let ident = &builder_field.ident;
+                let builder_type = builder_field.builder_type();
+                quote! {
+                    #ident: #builder_type
+                }
+            })
+            .chain(self.marker_field());
+        self.base.data = Data::Struct(DataStruct {
+            struct_token: Default::default(),
+            fields: Fields::Named(parse_quote!({ #(#fields),* })),
+            semi_token: None,
         });
+    }
+
+    // enumのvariantのbuilderだけが持つ、使わないジェネリクスのためのフィールド
+    fn marker_field(&self) -> Option<TokenStream> {
+        let marker = self.target.marker.as_ref()?;
+        let serde_skip = self.options.serde.is_some().then(|| quote!(#[serde(skip)]));
+        Some(quote! {
+            #serde_skip
+            __marker: ::core::marker::PhantomData<#marker>
+        })
+    }
 
-        Ok(())
+    fn marker_init(&self) -> Option<TokenStream> {
+        self.target
+            .marker
+            .as_ref()
+            .map(|_| quote!(__marker: ::core::marker::PhantomData))
     }
 
-    pub fn build(&mut self, original_input: &DeriveInput) -> syn::Result<TokenStream> {
+    pub fn build(&mut self, original_input: &DeriveInput) -> TokenStream {
         self.set_builder_name(original_input);
-        self.convert_fields_into_builder()?;
-        self.set_derive_attributes();
+        self.convert_fields_into_builder();
+        self.set_attributes();
 
-        let accessor = Self::accessor(original_input)?;
+        let default_impl = self.default_impl();
+        let accessor = self.accessor(original_input);
         let build_fn = self.build_fn(original_input);
+        let env_impl = self.env_impl(original_input);
 
         // builderを作るときに
         let base = self.base.clone();
 
-        Ok(quote! {
+        quote! {
           #base
 
+          #default_impl
+
           #accessor
 
           #build_fn
-        })
-    }
-
-    // #[builder(each = "arg")]みたいな形式をdetectする
-    fn extract_arg_name(attr: &Attribute) -> syn::Result<Ident> {
-        let builder: Expr = attr.parse_args().map_err(|_| {
-            syn::Error::new_spanned(attr.meta.clone(), r#"expected `builder(each = "...")`"#)
-        })?;
-        let Expr::Assign(assign) = builder else {
-            return Err(syn::Error::new_spanned(
-                attr.meta.clone(),
-                r#"expected `builder(each = "...")`"#,
-            ));
-        };
-
-        if !assign.attrs.is_empty() {
-            return Err(syn::Error::new_spanned(
-                attr.meta.clone(),
-                r#"expected `builder(each = "...")`"#,
-            ));
-        }
-
-        let Expr::Path(expr_path) = *assign.left else {
-            return Err(syn::Error::new_spanned(
-                attr.meta.clone(),
-                r#"expected `builder(each = "...")`"#,
-            ));
-        };
-
-        if !(expr_path.attrs.is_empty() && expr_path.qself.is_none()) {
-            return Err(syn::Error::new_spanned(
-                attr.meta.clone(),
-                r#"expected `builder(each = "...")`"#,
-            ));
-        }
 
-        if !expr_path
-            .path
-            .get_ident()
-            .is_some_and(|ident| ident == "each")
-        {
-            return Err(syn::Error::new_spanned(
-                attr.meta.clone(),
-                r#"expected `builder(each = "...")`"#,
-            ));
+          #env_impl
         }
-
-        let Expr::Lit(lit) = *assign.right else {
-            return Err(syn::Error::new_spanned(
-                attr.clone(),
-                r#"expected `builder(each = "...")`"#,
-            ));
-        };
-        let Lit::Str(lit_str) = lit.lit else {
-            return Err(syn::Error::new_spanned(
-                attr.clone(),
-                r#"expected `builder(each = "...")`"#,
-            ));
-        };
-        Ok(Ident::new(lit_str.value().as_str(), Span::call_site()))
     }
 
-    fn is_attribute_builder(attr: &Attribute) -> bool {
-        attr.path().is_ident("builder")
-    }
-
-    fn accessor(original_input: &DeriveInput) -> syn::Result<TokenStream> {
-        let fields = Self::extract_original_fields(original_input);
+    fn accessor(&self, original_input: &DeriveInput) -> TokenStream {
+        let fields = &self.fields;
         let methods = fields
             .iter()
+            .filter(|field| !field.is_skipped())
             .map(|field| {
-                let ident = &field.ident.clone().unwrap();
-                let ty = &field.ty;
-                let tokens = if is_option(ty) {
-                    let wraped_type = extract_type_from_option(ty);
+                let ident = &field.ident;
+                let doc_name = field.doc_name();
+                let each_setter = field.each_setter().map(|(name, args)| {
+                    let docs = field.docs_with(&format!("Adds one item to {doc_name}."));
+                    let setter = self.setter_method(&field.setter_vis, name, args, |builder| {
+                        field.extend_item(quote!(#builder.#ident))
+                    });
+                    quote!(#docs #setter)
+                });
+                let extend_setter = field.extend_setter().map(|(name, args)| {
+                    let docs = field.docs_with(&format!("Adds every item to {doc_name}."));
+                    let setter = self.setter_method(&field.setter_vis, &name, args, |builder| {
+                        field.extend_items(quote!(#builder.#ident))
+                    });
+                    quote!(#docs #setter)
+                });
+                // sub_builderのフィールドは、値のsetterの代わりに中のbuilderを触るメソッドを作る
+                if let Some(sub_builder) = &field.sub_builder {
+                    let builder_type = &sub_builder.builder_type;
+                    let setter_name = &field.setter_name;
+                    let accessor_name = format_ident!("{}_mut", setter_name.unraw());
+                    let vis = &field.setter_vis;
+                    let accessor_docs =
+                        field.docs_with(&format!("Returns the nested builder for {doc_name}."));
+                    let setter_docs = field.setter_docs();
+                    let is_set_name = field.method_name("is_set_");
+                    let is_set_docs = format!(
+                        "Returns whether any field of the nested builder for {doc_name} has been set."
+                    );
+                    let clear_docs = format!("Replaces {doc_name} with an empty nested builder.");
+                    // クロージャは中のbuilderのsetterと同じ受け取り方をする
+                    let (closure, apply): (TokenStream, fn(TokenStream) -> TokenStream) =
+                        match sub_builder.pattern {
+                            BuilderPattern::Mutable => (
+                                quote!(::core::ops::FnOnce(&mut #builder_type) -> &mut #builder_type),
+                                |inner| quote!(f(&mut #inner);),
+                            ),
+                            BuilderPattern::Owned => (
+                                quote!(::core::ops::FnOnce(#builder_type) -> #builder_type),
+                                |inner| quote!(#inner = f(::core::mem::take(&mut #inner));),
+                            ),
+                            BuilderPattern::Immutable => (
+                                quote!(::core::ops::FnOnce(&#builder_type) -> #builder_type),
+                                |inner| quote!(#inner = f(&#inner);),
+                            ),
+                        };
+                    let setter = self.setter_method(
+                        &field.setter_vis,
+                        setter_name,
+                        quote!(f: impl #closure),
+                        |builder| apply(quote!(#builder.#ident)),
+                    );
+                    let clear = self.setter_method(
+                        &field.setter_vis,
+                        &field.method_name("clear_"),
+                        quote!(),
+                        |builder| {
+                            quote! {
+                                #builder.#ident = ::core::default::Default::default();
+                            }
+                        },
+                    );
+                    return quote! {
+                        #accessor_docs
+                        #vis fn #accessor_name(&mut self) -> &mut #builder_type {
+                            &mut self.#ident
+                        }
+
+                        #setter_docs
+                        #setter
+
+                        #[doc = #is_set_docs]
+                        pub fn #is_set_name(&self) -> bool {
+                            self.#ident.__is_any_set()
+                        }
+
+                        #[doc = #clear_docs]
+                        #clear
+                    };
+                }
+                let bulk_setter = field.has_bulk_setter().then(|| {
+                    let setter_name = &field.setter_name;
+                    let (arg_type, value) = field.setter_argument();
+                    let setter = self.setter_method(
+                        &field.setter_vis,
+                        setter_name,
+                        quote!(#ident: #arg_type),
+                        |builder| {
+                            quote! {
+                                #builder.#ident = #value;
+                            }
+                        },
+                    );
+                    let (receiver, output) = match self.options.pattern() {
+                        BuilderPattern::Mutable => (quote!(&mut self), quote!(&mut Self)),
+                        BuilderPattern::Owned => (quote!(self), quote!(Self)),
+                        BuilderPattern::Immutable => (quote!(&self), quote!(Self)),
+                    };
+                    let try_setter = field.try_setter(receiver, output);
+                    let docs = field.setter_docs();
                     quote! {
-                      pub fn #ident(&mut self, #ident: #wraped_type) -> &mut Self {
-                          self.#ident = Some(#ident);
-                          self
-                      }
-                    }
-                } else if let Some(attr) = field
-                    .attrs
-                    .clone()
-                    .into_iter()
-                    .find(Self::is_attribute_builder)
-                {
-                    if !is_vector(&field.ty) {
-                        panic!("Vec<T> is expected")
+                      #docs
+                      #setter
+
+                      #try_setter
                     }
-                    let arg_name = Self::extract_arg_name(&attr)?;
-                    let vec_inner_ty = extract_type_from_vector(&field.ty);
-                    quote! {
-                      pub fn #arg_name(&mut self, #arg_name: #vec_inner_ty) -> &mut Self {
-                        self.#ident.push(#arg_name);
-                        self
-                      }
+                });
+                let is_set_name = field.method_name("is_set_");
+                let getter_name = field.method_name("get_");
+                let ty = &field.ty;
+                let clear = self.setter_method(
+                    &field.setter_vis,
+                    &field.method_name("clear_"),
+                    quote!(),
+                    |builder| {
+                        quote! {
+                            #builder.#ident = ::core::option::Option::None;
+                        }
+                    },
+                );
+                let is_set_docs = format!("Returns whether {doc_name} has been set.");
+                let getter_docs = format!("Returns the value set for {doc_name}, if any.");
+                let clear_docs = format!("Unsets {doc_name}, as if it had never been set.");
+                let tokens = quote! {
+                    #bulk_setter
+
+                    #each_setter
+
+                    #extend_setter
+
+                    #[doc = #is_set_docs]
+                    pub fn #is_set_name(&self) -> bool {
+                        self.#ident.is_some()
                     }
-                } else {
-                    quote! {
-                      pub fn #ident(&mut self, #ident: #ty) -> &mut Self {
-                          self.#ident = Some(#ident);
-                          self
-                      }
+
+                    #[doc = #getter_docs]
+                    pub fn #getter_name(&self) -> ::core::option::Option<&#ty> {
+                        self.#ident.as_ref()
                     }
+
+                    #[doc = #clear_docs]
+                    #clear
                 };
 
-                Ok(tokens)
+                tokens
             })
-            .collect::<syn::Result<Vec<_>>>()?;
-        let builder_name = builder_name(original_input);
-        Ok(quote! {
-            impl #builder_name {
+            .collect::<Vec<_>>();
+        // 設定を重ねる時のために、otherでセットされているフィールドで上書きする
+        // 中身は__merge_from()に置いて、merge()はpatternに合わせてそれを呼ぶだけにする
+        // sub_builderを持つ外側のbuilderは、中のbuilderのpatternに関係なく__merge_from()を呼べる
+        let merge_fields = fields
+            .iter()
+            .filter(|field| !field.is_skipped())
+            .map(|field| field.merge_field(quote!(self)));
+        // sub_builderのis_set_<field>()のために、どれか1つでもセットされているかを返す
+        let any_set = fields
+            .iter()
+            .filter(|field| !field.is_skipped())
+            .map(|field| {
+                let ident = &field.ident;
+                if field.sub_builder.is_some() {
+                    quote!(self.#ident.__is_any_set())
+                } else {
+                    quote!(self.#ident.is_some())
+                }
+            });
+        let merge = self.setter_method(
+            &parse_quote!(pub),
+            &format_ident!("merge"),
+            quote!(other: Self),
+            |builder| quote!(#builder.__merge_from(other);),
+        );
+        let builder_name = self.options.builder_name(original_input);
+        let (impl_generics, ty_generics, where_clause) = original_input.generics.split_for_impl();
+        quote! {
+            impl #impl_generics #builder_name #ty_generics #where_clause {
                 #(#methods)*
+
+                /// Copies every field that is set in `other` into this builder.
+                #merge
+
+                #[doc(hidden)]
+                pub fn __merge_from(&mut self, other: Self) {
+                    #(#merge_fields)*
+                }
+
+                #[doc(hidden)]
+                pub fn __is_any_set(&self) -> bool {
+                    false #(|| #any_set)*
+                }
             }
-        })
+        }
     }
 
-    fn set_derive_attributes(&mut self) {
-        let attr = parse_quote! {
-            #[derive(std::default::Default, std::fmt::Debug, std::clone::Clone)]
-        };
-        self.base.attrs = vec![attr]
+    // 既にある値から、全てのフィールドがセットされたbuilderを作る
+    pub fn impl_from(&self, original_input: &DeriveInput) -> TokenStream {
+        let fields = &self.fields;
+        let builder_name = self.options.builder_name(original_input);
+        let (impl_generics, ty_generics, where_clause) = original_input.generics.split_for_impl();
+        let BuildTarget {
+            ty: target_type,
+            path: target_path,
+            ..
+        } = &self.target;
+        let bindings = fields.iter().map(BuilderField::binding);
+        let stored = fields
+            .iter()
+            .filter(|field| !field.is_skipped())
+            .map(BuilderField::stored_binding)
+            .chain(self.marker_init());
+        quote! {
+            impl #impl_generics ::core::convert::From<#target_type> for #builder_name #ty_generics #where_clause {
+                fn from(value: #target_type) -> Self {
+                    let #target_path { #(#bindings),* } = value;
+                    Self {
+                        #(#stored),*
+                    }
+                }
+            }
+        }
     }
 
-    fn extract_original_fields(original_input: &DeriveInput) -> Vec<Field> {
-        let Data::Struct(data_struct) = &original_input.data else {
-            panic!("Should be Struct")
-        };
-        let syn::Fields::Named(fields_named) = &data_struct.fields else {
-            panic!("field name is necessary")
-        };
-
-        fields_named.named.iter().cloned().collect::<Vec<_>>()
+    // patternに合わせたsetter。assignはbuilderを受け取って、値をセットする文を返す
+    fn setter_method(
+        &self,
+        vis: &Visibility,
+        name: &Ident,
+        args: TokenStream,
+        assign: impl Fn(TokenStream) -> TokenStream,
+    ) -> TokenStream {
+        match self.options.pattern() {
+            BuilderPattern::Mutable => {
+                let assign = assign(quote!(self));
+                quote! {
+                    #vis fn #name(&mut self, #args) -> &mut Self {
+                        #assign
+                        self
+                    }
+                }
+            }
+            BuilderPattern::Owned => {
+                let assign = assign(quote!(self));
+                quote! {
+                    #vis fn #name(mut self, #args) -> Self {
+                        #assign
+                        self
+                    }
+                }
+            }
+            BuilderPattern::Immutable => {
+                // 引数と名前がぶつからないよう、__defaultや__builtと同じく__を付ける
+                let assign = assign(quote!(__builder));
+                quote! {
+                    #vis fn #name(&self, #args) -> Self
+                    where
+                        Self: ::core::clone::Clone,
+                    {
+                        let mut __builder = ::core::clone::Clone::clone(self);
+                        #assign
+                        __builder
+                    }
+                }
+            }
+        }
     }
 
-    fn extract_fields(&self) -> Vec<Field> {
-        let Data::Struct(data_struct) = &self.base.data else {
-            panic!("Should be Struct")
-        };
-        let syn::Fields::Named(fields_named) = &data_struct.fields else {
-            panic!("field name is necessary")
+    // 元のstructのattributeは持ってこず、ドキュメントとderiveだけ付ける
+    fn set_attributes(&mut self) {
+        let fields = &self.fields;
+        let summary = format!("Builder for {}.", self.target.doc_link());
+        let required = BuilderField::doc_names(fields.iter().filter(|field| field.is_required()));
+        let details = if required.is_empty() {
+            "Every field is optional.".to_owned()
+        } else {
+            format!("Required fields: {required}.")
         };
+        let derive = self.options.builder_derive_attributes();
+        self.base.attrs = vec![
+            parse_quote!(#[doc = #summary]),
+            parse_quote!(#[doc = ""]),
+            parse_quote!(#[doc = #details]),
+        ];
+        self.base.attrs.extend(derive);
+    }
 
-        fields_named.named.iter().cloned().collect::<Vec<_>>()
+    // derive(Default)だと全ての型パラメータに`T: Default`が付いてしまうので手で実装する
+    fn default_impl(&self) -> TokenStream {
+        let fields = &self.fields;
+        let field_inits = fields
+            .iter()
+            .filter(|field| !field.is_skipped())
+            .map(|field| {
+                let ident = &field.ident;
+                quote!(#ident: ::core::default::Default::default())
+            })
+            .chain(self.marker_init());
+        let builder_name = &self.base.ident;
+        let (impl_generics, ty_generics, where_clause) = self.base.generics.split_for_impl();
+        quote! {
+            impl #impl_generics ::core::default::Default for #builder_name #ty_generics #where_clause {
+                fn default() -> Self {
+                    Self {
+                        #(#field_inits),*
+                    }
+                }
+            }
+        }
     }
 
     fn build_fn(&self, original_input: &DeriveInput) -> TokenStream {
-        // originalとタイプが同じものは弾くか？
-        let fields = self.extract_fields();
-        let field_names = fields
-            .into_iter()
-            .map(|field| field.ident.unwrap())
+        let original_fields = &self.fields;
+        // setter(skip)のフィールドはbuilderに無いので、元のstructのフィールド名を使う
+        let field_names = original_fields
+            .iter()
+            .map(|field| field.ident.clone())
             .collect::<Vec<_>>();
+        let members = original_fields.iter().map(|field| &field.member);
 
-        let original_fields = Self::extract_original_fields(original_input);
+        let error_name = self.options.builder_error_name(original_input);
+        // #[builder(error = "...")]があれば、From<FooBuilderError>で変換する
+        let error_type = self
+            .options
+            .error
+            .as_ref()
+            .map_or_else(|| quote!(#error_name), |error| quote!(#error));
 
-        // ここで、もともとoptionだったものは無視していい
-        let field_checks = original_fields
+        // 最初に見つかった1つだけでなく、未設定のフィールドを宣言順に全部集める
+        let missing_checks = original_fields
             .iter()
-            .map(|original_field| {
-                let field_name = original_field.ident.clone().unwrap();
-                match is_option(&original_field.ty)
-                    || (is_vector(&original_field.ty)
-                        && original_field.attrs.iter().any(Self::is_attribute_builder))
-                {
-                    true => {
+            .filter(|field| field.is_required())
+            .map(|field| {
+                let field_name = &field.ident;
+                let field_str = field.name_str();
+                quote! {
+                    if self.#field_name.is_none() {
+                        missing_fields.push(#field_str);
+                    }
+                }
+            });
+
+        // 本体は、フィールドをcloneする__build_ref(&self)と、moveする__build_owned(self)の2つに置く
+        // build()はpatternに合わせてどちらかを呼ぶだけにする
+        // sub_builderのフィールドも同じ方を呼ぶので、中のbuilderのpatternに関係なくbuildできる
+        let field_checks = |owned: bool| {
+            original_fields
+                .iter()
+                .map(|field| {
+                    let field_name = &field.ident;
+                    // ここで、もともとoptionだったものやdefault指定があるものは、未設定ならその値を使う
+                    let unset_value = field
+                        .unset_value
+                        .clone()
+                        .unwrap_or_else(|| quote!(::core::unreachable!()));
+                    if field.is_skipped() {
                         quote! {
-                            let #field_name = self.#field_name.clone();
+                            let #field_name = #unset_value;
                         }
-                    }
-                    false => {
+                    } else if let Some(sub_builder) = &field.sub_builder {
+                        let variant = &sub_builder.variant;
+                        let build = if owned {
+                            quote!(__build_owned)
+                        } else {
+                            quote!(__build_ref)
+                        };
+                        quote! {
+                            let #field_name = match self.#field_name.#build() {
+                                ::core::result::Result::Ok(value) => value,
+                                ::core::result::Result::Err(error) => {
+                                    return ::core::result::Result::Err(::core::convert::From::from(
+                                        #error_name::#variant(error),
+                                    ));
+                                }
+                            };
+                        }
+                    } else {
+                        let value = if owned {
+                            quote!(self.#field_name)
+                        } else {
+                            quote!(::core::clone::Clone::clone(&self.#field_name))
+                        };
                         quote! {
-                            let #field_name = self.#field_name.clone().map_or_else(|| {
-                                Err(format!("{} is not set", stringify!(#field_name)))
-                            }, Ok)?;
+                            let #field_name = match #value {
+                                ::core::option::Option::Some(value) => value,
+                                ::core::option::Option::None => #unset_value,
+                            };
                         }
                     }
+                })
+                .collect::<Vec<_>>()
+        };
+        // validate = "..."の付いたフィールドは、値が決まった所で宣言順に確かめる
+        let field_validations = original_fields.iter().filter_map(|field| {
+            let validate = field.options.validate.as_ref()?;
+            let field_name = &field.ident;
+            let field_str = field.name_str();
+            Some(quote! {
+                if let ::core::result::Result::Err(message) = #validate(&#field_name) {
+                    return ::core::result::Result::Err(::core::convert::From::from(
+                        #error_name::InvalidField { field: #field_str, message },
+                    ));
                 }
             })
-            .collect::<Vec<_>>();
-        let builder_name = builder_name(original_input);
-        let original_name = original_input.ident.clone();
+        });
+        let struct_validation = self.options.validate.as_ref().map(|validate| {
+            quote! {
+                if let ::core::result::Result::Err(message) = #validate(&__built) {
+                    return ::core::result::Result::Err(::core::convert::From::from(
+                        #error_name::Validation(message),
+                    ));
+                }
+            }
+        });
+
+        // __build_ref()はフィールドをcloneするので、フィールドの型ごとにClone境界を足す
+        let mut generics = original_input.generics.clone();
+        let clone_where_clause = generics.make_where_clause();
+        original_fields
+            .iter()
+            .filter(|field| !field.is_skipped() && field.sub_builder.is_none())
+            .for_each(|field| {
+                clone_where_clause
+                    .predicates
+                    .push(deferred_clone_bound(&field.ty))
+            });
+        let (_, _, clone_where_clause) = generics.split_for_impl();
+        let (impl_generics, ty_generics, where_clause) = original_input.generics.split_for_impl();
+
+        let builder_name = self.options.builder_name(original_input);
+        let BuildTarget {
+            ty: target_type,
+            path: target_path,
+            ..
+        } = &self.target;
+
+        // #[builder(default)]がstructに付いていたら、未設定のフィールドはここから持ってくる
+        let struct_default = self.options.default.then(|| {
+            quote! {
+                let __default: #target_type = ::core::default::Default::default();
+            }
+        });
+
+        let alloc = self.options.alloc_crate();
+        let missing_checks = missing_checks.collect::<Vec<_>>();
+        let field_validations = field_validations.collect::<Vec<_>>();
+        let members = members.collect::<Vec<_>>();
+        let body = |owned: bool| {
+            let field_checks = field_checks(owned);
+            quote! {
+                #[allow(unused_mut)]
+                let mut missing_fields: #alloc::vec::Vec<&'static str> = #alloc::vec::Vec::new();
+                #(#missing_checks)*
+                if !missing_fields.is_empty() {
+                    return ::core::result::Result::Err(::core::convert::From::from(
+                        #error_name::MissingFields { fields: missing_fields },
+                    ));
+                }
+
+                #struct_default
+                #(#field_checks)*
+                #(#field_validations)*
+
+                let __built = #target_path {
+                    #(#members: #field_names),*
+                };
+                #struct_validation
+                ::core::result::Result::Ok(__built)
+            }
+        };
+        let build_ref = body(false);
+        let build_owned = body(true);
+
+        // owned patternならフィールドをmoveし、それ以外はcloneする
+        // __build_ref()などは生成したエラーを返すので、外側のbuilderはsub_builderのエラーを
+        // error = "..."に関係なくFooBuilderErrorとして受け取れる。build()だけがerrorに変換する
+        let docs = self.build_docs(original_fields);
+        let result = quote!(::core::result::Result<#target_type, #error_type>);
+        let inner_result = quote!(::core::result::Result<#target_type, #error_name>);
+        let convert = |built: TokenStream| match &self.options.error {
+            Some(_) => quote!(::core::result::Result::map_err(#built, ::core::convert::From::from)),
+            None => built,
+        };
+        let (build_by_ref, build_by_value) = match self.options.pattern() {
+            BuilderPattern::Mutable => {
+                let built = convert(quote!(self.__build_ref()));
+                (
+                    Some(quote!(#docs pub fn build(&mut self) -> #result { #built })),
+                    None,
+                )
+            }
+            BuilderPattern::Immutable => {
+                let built = convert(quote!(self.__build_ref()));
+                (
+                    Some(quote!(#docs pub fn build(&self) -> #result { #built })),
+                    None,
+                )
+            }
+            BuilderPattern::Owned => {
+                let built = convert(quote!(self.__build_owned()));
+                (
+                    None,
+                    Some(quote!(#docs pub fn build(self) -> #result { #built })),
+                )
+            }
+        };
+
         quote! {
-            impl #builder_name {
-                pub fn build(&mut self) -> std::result::Result<#original_name, std::boxed::Box<dyn std::error::Error>> {
-                        #(#field_checks)*
+            impl #impl_generics #builder_name #ty_generics #clone_where_clause {
+                #build_by_ref
+
+                #[doc(hidden)]
+                pub fn __build_ref(&self) -> #inner_result {
+                    #build_ref
+                }
+            }
+
+            impl #impl_generics #builder_name #ty_generics #where_clause {
+                #build_by_value
+
+                #[doc(hidden)]
+                pub fn __build_owned(self) -> #inner_result {
+                    #build_owned
+                }
+            }
+        }
+    }
+
+    // builder(env)の時、文字列のkeyと値からフィールドをセットするapply_kv()とfrom_env()
+    // 値はFromStrで変換して、直接builderのフィールドに入れる
+    fn env_impl(&self, original_input: &DeriveInput) -> Option<TokenStream> {
+        self.options.env?;
+        let fields = self
+            .fields
+            .iter()
+            .filter_map(|field| Some((field, field.env_key.as_ref()?)))
+            .collect::<Vec<_>>();
+        let alloc = self.options.alloc_crate();
+        let error_name = self.options.builder_error_name(original_input);
+
+        // フィールドの型がジェネリクスでも使えるよう、FromStrで変換できることを境界にする
+        // FromStrでない型だとここでエラーになるので、env(skip)を付けるべきフィールドを指すようにする
+        let mut generics = original_input.generics.clone();
+        let where_clause = generics.make_where_clause();
+        for (field, _) in &fields {
+            let ty = field.setter_value_type();
+            let span = field.ty.span();
+            where_clause
+                .predicates
+                .push(parse_quote_spanned!(span=> #ty: ::core::str::FromStr));
+            where_clause.predicates.push(parse_quote_spanned!(span=>
+                <#ty as ::core::str::FromStr>::Err: ::core::fmt::Display
+            ));
+        }
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+        let (_, ty_generics, _) = original_input.generics.split_for_impl();
+        let builder_name = self.options.builder_name(original_input);
+
+        let arms = fields.iter().map(|(field, key)| {
+            let ident = &field.ident;
+            let ty = field.setter_value_type();
+            let field_str = field.name_str();
+            let (_, value) = field.setter_argument();
+            quote! {
+                #key => match <#ty as ::core::str::FromStr>::from_str(value) {
+                    ::core::result::Result::Ok(#ident) => self.#ident = #value,
+                    ::core::result::Result::Err(error) => errors.push((
+                        #field_str,
+                        #alloc::string::ToString::to_string(&error),
+                    )),
+                },
+            }
+        });
+        let keys = fields
+            .iter()
+            .map(|(_, key)| format!("`{key}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let apply_docs = format!("Keys: {keys}. Any other key is ignored.");
+
+        // std::envを使うので、no_stdの時はapply_kv()だけ作る
+        let from_env = (!self.options.no_std).then(|| {
+            quote! {
+                /// Creates a builder from the environment variables whose names start with
+                /// `prefix`, reading the rest of each name as a key for `apply_kv()`.
+                ///
+                /// Variables that are not valid Unicode are ignored.
+                ///
+                /// # Errors
+                ///
+                /// Fails if any value cannot be parsed.
+                pub fn from_env(prefix: &str) -> ::core::result::Result<Self, #error_name> {
+                    let mut builder = <Self as ::core::default::Default>::default();
+                    builder.apply_kv(::std::env::vars_os().filter_map(|(key, value)| {
+                        let key = key.into_string().ok()?;
+                        let value = value.into_string().ok()?;
+                        let key = key.strip_prefix(prefix)?;
+                        ::core::option::Option::Some((::std::string::String::from(key), value))
+                    }))?;
+                    ::core::result::Result::Ok(builder)
+                }
+            }
+        });
 
-                    Ok(#original_name {
-                        #(#field_names),*
-                    })
+        Some(quote! {
+            impl #impl_generics #builder_name #ty_generics #where_clause {
+                /// Sets fields from key/value pairs, parsing each value with `FromStr`.
+                ///
+                #[doc = #apply_docs]
+                ///
+                /// # Errors
+                ///
+                /// Fails if any value cannot be parsed. Every such field is reported, and the
+                /// fields that could be parsed are still set.
+                pub fn apply_kv<__Key, __Value>(
+                    &mut self,
+                    items: impl ::core::iter::IntoIterator<Item = (__Key, __Value)>,
+                ) -> ::core::result::Result<(), #error_name>
+                where
+                    __Key: ::core::convert::AsRef<str>,
+                    __Value: ::core::convert::AsRef<str>,
+                {
+                    let mut errors: #alloc::vec::Vec<(&'static str, #alloc::string::String)> =
+                        #alloc::vec::Vec::new();
+                    for (key, value) in items {
+                        let value = value.as_ref();
+                        match key.as_ref() {
+                            #(#arms)*
+                            _ => {}
+                        }
+                    }
+                    if errors.is_empty() {
+                        ::core::result::Result::Ok(())
+                    } else {
+                        ::core::result::Result::Err(#error_name::InvalidValues { errors })
+                    }
                 }
+
+                #from_env
             }
+        })
+    }
+
+    // build()のドキュメント。どういう時にエラーになるかを並べる
+    fn build_docs(&self, fields: &[BuilderField]) -> TokenStream {
+        let summary = format!(
+            "Builds a {} from the fields set so far.",
+            self.target.doc_link()
+        );
+        let required = BuilderField::doc_names(fields.iter().filter(|field| field.is_required()));
+        let mut errors = Vec::new();
+        if !required.is_empty() {
+            errors.push(format!(
+                "Fails if any of these fields is not set: {required}."
+            ));
+        }
+        let nested =
+            BuilderField::doc_names(fields.iter().filter(|field| field.sub_builder.is_some()));
+        if !nested.is_empty() {
+            errors.push(format!("Fails if the nested builder of {nested} fails."));
+        }
+        let validated = BuilderField::doc_names(
+            fields
+                .iter()
+                .filter(|field| field.options.validate.is_some()),
+        );
+        if !validated.is_empty() {
+            errors.push(format!("Fails if {validated} does not pass validation."));
+        }
+        if self.options.validate.is_some() {
+            errors.push("Fails if the built value does not pass validation.".to_owned());
+        }
+        let errors = (!errors.is_empty()).then(|| {
+            quote! {
+                #[doc = ""]
+                #[doc = "# Errors"]
+                #[doc = ""]
+                #(#[doc = #errors])*
+            }
+        });
+        quote! {
+            #[doc = #summary]
+            #errors
         }
     }
 }
@@ -0,0 +1,467 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_quote, DeriveInput, GenericParam, Generics, Result};
+
+use crate::field::BuilderField;
+use crate::options::StructOptions;
+use crate::{generic_args, to_camel_case, BuildTarget, Errors};
+
+// #[builder(typestate)]のとき、必須フィールドがセットされたかどうかを型パラメータで管理する
+pub struct TypestateBuilderFactory {
+    base: DeriveInput,
+    options: StructOptions,
+    target: BuildTarget,
+    fields: Vec<TypestateField>,
+}
+
+struct TypestateField {
+    field: BuilderField,
+    // 必須フィールドだけ、セットされていない時の型と状態を表す型パラメータを持つ
+    state: Option<(Ident, Ident)>,
+}
+
+impl TypestateBuilderFactory {
+    pub fn new(original: DeriveInput, options: StructOptions, target: BuildTarget) -> Result<Self> {
+        let builder_name = options.builder_name(&original);
+        let fields = BuilderField::from_input(&original, &options)?;
+        let mut errors = Errors::default();
+        for field in &fields {
+            if let Some(validate) = &field.options.validate {
+                errors.push(syn::Error::new_spanned(
+                    validate,
+                    "a typestate builder cannot fail to build, so it cannot validate",
+                ));
+            }
+            if field.sub_builder.is_some() {
+                errors.push(syn::Error::new_spanned(
+                    &field.ty,
+                    "a typestate builder cannot fail to build, so it cannot have a `sub_builder`",
+                ));
+            }
+        }
+        errors.finish()?;
+        let fields = fields
+            .into_iter()
+            .map(|field| {
+                let state = field.is_required().then(|| {
+                    let camel = to_camel_case(&field.ident);
+                    (
+                        format_ident!("{}Missing{}", builder_name, camel),
+                        format_ident!("__{}", camel),
+                    )
+                });
+                TypestateField { field, state }
+            })
+            .collect();
+
+        Ok(Self {
+            base: original,
+            options,
+            target,
+            fields,
+        })
+    }
+
+    fn required(&self) -> impl Iterator<Item = (&Ident, &Ident)> {
+        self.fields.iter().filter_map(|field| {
+            field
+                .state
+                .as_ref()
+                .map(|(missing, state)| (missing, state))
+        })
+    }
+
+    // setter(skip)のフィールドはbuilderに持たない
+    fn stored(&self) -> impl Iterator<Item = &BuilderField> {
+        self.fields
+            .iter()
+            .map(|field| &field.field)
+            .filter(|field| !field.is_skipped())
+    }
+
+    fn set_marker(&self) -> Ident {
+        format_ident!("{}Set", self.options.builder_name(&self.base))
+    }
+
+    // 元のstructのジェネリクスに、状態を表す型パラメータを足したもの
+    fn builder_generics(&self) -> Generics {
+        let mut generics = self.base.generics.clone();
+        self.required().for_each(|(_, state)| {
+            generics
+                .params
+                .push(GenericParam::Type(parse_quote!(#state)));
+        });
+        generics
+    }
+
+    // 状態を表す型引数を指定したbuilderの型
+    fn builder_type_with(&self, states: Vec<TokenStream>) -> TokenStream {
+        let builder_name = self.options.builder_name(&self.base);
+        let args = generic_args(&self.base.generics);
+        quote! {
+            #builder_name<#(#args,)* #(#states),*>
+        }
+    }
+
+    // Foo::builder()が返す、全ての必須フィールドが未設定の型
+    pub fn builder_type(&self) -> TokenStream {
+        let states = self
+            .required()
+            .map(|(missing, _)| quote!(#missing))
+            .collect();
+        self.builder_type_with(states)
+    }
+
+    // 全ての必須フィールドがセットされた型。build()はこの型にだけ生える
+    pub fn filled_type(&self) -> TokenStream {
+        let set = self.set_marker();
+        let states = self.required().map(|_| quote!(#set)).collect();
+        self.builder_type_with(states)
+    }
+
+    pub fn build(&self) -> TokenStream {
+        let markers = self.markers();
+        let builder_struct = self.builder_struct();
+        let default_impl = self.default_impl();
+        let accessor = self.accessor();
+        let build_fn = self.build_fn();
+
+        quote! {
+            #markers
+
+            #builder_struct
+
+            #default_impl
+
+            #accessor
+
+            #build_fn
+        }
+    }
+
+    fn markers(&self) -> TokenStream {
+        let vis = &self.options.builder_vis(&self.base);
+        let builder_name = self.options.builder_name(&self.base);
+        let set = self.set_marker();
+        let set_docs = format!("Marks a required field of [`{builder_name}`] as set.");
+        let (missing, missing_docs): (Vec<_>, Vec<_>) = self
+            .fields
+            .iter()
+            .filter_map(|TypestateField { field, state }| {
+                let (missing, _) = state.as_ref()?;
+                let docs = format!(
+                    "Marks {} of [`{builder_name}`] as not set yet.",
+                    field.doc_name()
+                );
+                Some((missing, docs))
+            })
+            .unzip();
+        quote! {
+            #[doc = #set_docs]
+            #[derive(::core::fmt::Debug, ::core::clone::Clone, ::core::marker::Copy)]
+            #vis struct #set;
+
+            #(
+                #[doc = #missing_docs]
+                #[derive(::core::fmt::Debug, ::core::clone::Clone, ::core::marker::Copy)]
+                #vis struct #missing;
+            )*
+        }
+    }
+
+    // 必須フィールドを並べたドキュメント。`a`, `b`
+    fn required_docs(&self) -> String {
+        BuilderField::doc_names(
+            self.fields
+                .iter()
+                .filter(|typestate_field| typestate_field.state.is_some())
+                .map(|typestate_field| &typestate_field.field),
+        )
+    }
+
+    fn builder_struct(&self) -> TokenStream {
+        let vis = &self.options.builder_vis(&self.base);
+        let builder_name = self.options.builder_name(&self.base);
+        let generics = self.builder_generics();
+        let where_clause = &generics.where_clause;
+        let states = self.required().map(|(_, state)| state);
+        let derive = self.options.builder_derive_attributes();
+        let summary = format!("Builder for {}.", self.target.doc_link());
+        let required = self.required_docs();
+        let details = if required.is_empty() {
+            "Every field is optional.".to_owned()
+        } else {
+            format!(
+                "Required fields: {required}. `build()` is only available once all of them are set."
+            )
+        };
+        let fields = self.stored().map(|field| {
+            let ident = &field.ident;
+            let builder_type = field.builder_type();
+            quote! {
+                #ident: #builder_type
+            }
+        });
+        // enumのvariantのbuilderは、variantが使わないジェネリクスも状態と一緒に持っておく
+        let marker = &self.target.marker;
+        quote! {
+            #[doc = #summary]
+            #[doc = ""]
+            #[doc = #details]
+            #(#derive)*
+            #vis struct #builder_name #generics #where_clause {
+                #(#fields,)*
+                __state: ::core::marker::PhantomData<(#(#states,)* #marker)>,
+            }
+        }
+    }
+
+    fn default_impl(&self) -> TokenStream {
+        let (impl_generics, _, where_clause) = self.base.generics.split_for_impl();
+        let builder_type = self.builder_type();
+        let field_names = self.stored().map(|field| &field.ident);
+        quote! {
+            impl #impl_generics ::core::default::Default for #builder_type #where_clause {
+                fn default() -> Self {
+                    Self {
+                        #(#field_names: ::core::default::Default::default(),)*
+                        __state: ::core::marker::PhantomData,
+                    }
+                }
+            }
+        }
+    }
+
+    fn accessor(&self) -> TokenStream {
+        let generics = self.builder_generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let builder_name = self.options.builder_name(&self.base);
+        let set = self.set_marker();
+
+        let methods = self
+            .fields
+            .iter()
+            .filter(|typestate_field| !typestate_field.field.is_skipped())
+            .map(|TypestateField { field, state }| {
+                let ident = &field.ident;
+                let setter_name = &field.setter_name;
+                let vis = &field.setter_vis;
+                let (arg_type, value) = field.setter_argument();
+                let docs = field.setter_docs();
+                let doc_name = field.doc_name();
+                if field.is_repeated() {
+                    let each_setter = field.each_setter().map(|(name, args)| {
+                        let extend = field.extend_item(quote!(self.#ident));
+                        let docs = field.docs_with(&format!("Adds one item to {doc_name}."));
+                        quote! {
+                            #docs
+                            #vis fn #name(mut self, #args) -> Self {
+                                #extend
+                                self
+                            }
+                        }
+                    });
+                    let extend_setter = field.extend_setter().map(|(name, args)| {
+                        let extend = field.extend_items(quote!(self.#ident));
+                        let docs = field.docs_with(&format!("Adds every item to {doc_name}."));
+                        quote! {
+                            #docs
+                            #vis fn #name(mut self, #args) -> Self {
+                                #extend
+                                self
+                            }
+                        }
+                    });
+                    let bulk_setter = field.has_bulk_setter().then(|| {
+                        let try_setter = field.try_setter(quote!(self), quote!(Self));
+                        quote! {
+                            #docs
+                            #vis fn #setter_name(mut self, #ident: #arg_type) -> Self {
+                                self.#ident = #value;
+                                self
+                            }
+
+                            #try_setter
+                        }
+                    });
+                    quote! {
+                        #bulk_setter
+
+                        #each_setter
+
+                        #extend_setter
+                    }
+                } else if let Some((_, state)) = state {
+                    // このフィールドの状態だけをSetに変える
+                    let states = self
+                        .required()
+                        .map(|(_, other)| {
+                            if other == state {
+                                quote!(#set)
+                            } else {
+                                quote!(#other)
+                            }
+                        })
+                        .collect();
+                    let next = self.builder_type_with(states);
+                    let rest = self
+                        .stored()
+                        .map(|other| &other.ident)
+                        .filter(|other| *other != ident);
+                    let try_setter = field.try_setter(quote!(self), next.clone());
+                    quote! {
+                        #docs
+                        #vis fn #setter_name(self, #ident: #arg_type) -> #next {
+                            #builder_name {
+                                #ident: #value,
+                                #(#rest: self.#rest,)*
+                                __state: ::core::marker::PhantomData,
+                            }
+                        }
+
+                        #try_setter
+                    }
+                } else {
+                    let try_setter = field.try_setter(quote!(self), quote!(Self));
+                    quote! {
+                        #docs
+                        #vis fn #setter_name(mut self, #ident: #arg_type) -> Self {
+                            self.#ident = #value;
+                            self
+                        }
+
+                        #try_setter
+                    }
+                }
+            });
+
+        // 必須フィールドを未設定に戻すと型が変わってしまうので、clear_<field>()は必須でないものだけ
+        let inspectors = self
+            .fields
+            .iter()
+            .filter(|typestate_field| !typestate_field.field.is_skipped())
+            .map(|TypestateField { field, state }| {
+                let ident = &field.ident;
+                let ty = &field.ty;
+                let getter_name = field.method_name("get_");
+                let doc_name = field.doc_name();
+                let getter_docs = format!("Returns the value set for {doc_name}, if any.");
+                let clear = state.is_none().then(|| {
+                    let clear_name = field.method_name("clear_");
+                    let vis = &field.setter_vis;
+                    let docs = format!("Unsets {doc_name}, as if it had never been set.");
+                    quote! {
+                        #[doc = #docs]
+                        #vis fn #clear_name(mut self) -> Self {
+                            self.#ident = ::core::option::Option::None;
+                            self
+                        }
+                    }
+                });
+                quote! {
+                    #[doc = #getter_docs]
+                    pub fn #getter_name(&self) -> ::core::option::Option<&#ty> {
+                        self.#ident.as_ref()
+                    }
+
+                    #clear
+                }
+            });
+
+        quote! {
+            impl #impl_generics #builder_name #ty_generics #where_clause {
+                #(#methods)*
+
+                #(#inspectors)*
+            }
+        }
+    }
+
+    // 全ての必須フィールドがSetになっている時だけbuild()が生える
+    fn build_fn(&self) -> TokenStream {
+        let (impl_generics, _, where_clause) = self.base.generics.split_for_impl();
+        let builder_type = self.filled_type();
+        let BuildTarget {
+            ty: target_type,
+            path: target_path,
+            ..
+        } = &self.target;
+
+        let fields = self.fields.iter().map(|TypestateField { field, .. }| {
+            let ident = &field.ident;
+            let member = &field.member;
+            let unset_value = field
+                .unset_value
+                .clone()
+                .unwrap_or_else(|| quote!(::core::unreachable!()));
+            if field.is_skipped() {
+                quote! {
+                    #member: #unset_value
+                }
+            } else {
+                quote! {
+                    #member: match self.#ident {
+                        ::core::option::Option::Some(value) => value,
+                        ::core::option::Option::None => #unset_value,
+                    }
+                }
+            }
+        });
+        let struct_default = self.options.default.then(|| {
+            quote! {
+                let __default: #target_type = ::core::default::Default::default();
+            }
+        });
+
+        let summary = format!("Builds a {}.", self.target.doc_link());
+        let required = self.required_docs();
+        let details = (!required.is_empty()).then(|| {
+            let details = format!("Only available once these fields are set: {required}.");
+            quote! {
+                #[doc = ""]
+                #[doc = #details]
+            }
+        });
+
+        quote! {
+            impl #impl_generics #builder_type #where_clause {
+                #[doc = #summary]
+                #details
+                pub fn build(self) -> #target_type {
+                    #struct_default
+                    #target_path {
+                        #(#fields,)*
+                    }
+                }
+            }
+        }
+    }
+
+    // 既にある値から、全てのフィールドがセットされたbuilderを作る
+    pub fn impl_from(&self) -> TokenStream {
+        let (impl_generics, _, where_clause) = self.base.generics.split_for_impl();
+        let builder_type = self.filled_type();
+        let builder_name = self.options.builder_name(&self.base);
+        let BuildTarget {
+            ty: target_type,
+            path: target_path,
+            ..
+        } = &self.target;
+        let bindings = self
+            .fields
+            .iter()
+            .map(|TypestateField { field, .. }| field.binding());
+        let stored = self.stored().map(BuilderField::stored_binding);
+        quote! {
+            impl #impl_generics ::core::convert::From<#target_type> for #builder_type #where_clause {
+                fn from(value: #target_type) -> Self {
+                    let #target_path { #(#bindings),* } = value;
+                    #builder_name {
+                        #(#stored,)*
+                        __state: ::core::marker::PhantomData,
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// Generic structs should get a builder carrying the same generics: type
+// parameters, lifetimes, const generics and the where clause all need to show
+// up on every generated impl, and build() returns the fully parameterized type.
+
+use derive_builder::Builder;
+use std::fmt::Display;
+
+#[derive(Builder)]
+pub struct Config<'a, B, const N: usize>
+where
+    B: Display,
+{
+    name: &'a str,
+    backend: B,
+    ports: [u16; N],
+    #[builder(each = "tag")]
+    tags: Vec<&'a str>,
+    comment: Option<B>,
+}
+
+// The backend type is neither Default nor Debug.
+#[derive(Clone)]
+struct Backend;
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("backend")
+    }
+}
+
+fn main() {
+    let name = String::from("primary");
+    let config: Config<Backend, 2> = Config::builder()
+        .name(&name)
+        .backend(Backend)
+        .ports([80, 443])
+        .tag("web")
+        .build()
+        .unwrap();
+
+    assert_eq!(config.name, "primary");
+    assert_eq!(config.backend.to_string(), "backend");
+    assert_eq!(config.ports, [80, 443]);
+    assert_eq!(config.tags, vec!["web"]);
+    assert!(config.comment.is_none());
+}
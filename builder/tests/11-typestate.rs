@@ -0,0 +1,44 @@
+// With #[builder(typestate)] the builder tracks in its type which required
+// fields have been set. build() only exists once all of them are provided and
+// returns the struct directly instead of a Result.
+
+use derive_builder::Builder;
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(typestate)]
+pub struct Command<T> {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    current_dir: Option<String>,
+    timeout: T,
+}
+
+fn main() {
+    let command: Command<u32> = Command::builder()
+        .arg("build".to_owned())
+        .executable("cargo".to_owned())
+        .timeout(30)
+        .arg("--release".to_owned())
+        .build();
+
+    assert_eq!(
+        command,
+        Command {
+            executable: "cargo".to_owned(),
+            args: vec!["build".to_owned(), "--release".to_owned()],
+            current_dir: None,
+            timeout: 30,
+        }
+    );
+
+    // Setting a required field twice keeps the last value.
+    let command = Command::builder()
+        .executable("rustc".to_owned())
+        .current_dir("..".to_owned())
+        .timeout(())
+        .executable("cargo".to_owned())
+        .build();
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(command.current_dir.as_deref(), Some(".."));
+}
@@ -0,0 +1,19 @@
+// Forgetting a required field in typestate mode is a compile error rather
+// than a runtime one, and the builder's type names the missing field.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Command {
+    executable: String,
+    current_dir: Option<String>,
+    timeout: u32,
+}
+
+fn main() {
+    let _command = Command::builder()
+        .executable("cargo".to_owned())
+        .current_dir("..".to_owned())
+        .build();
+}
@@ -0,0 +1,54 @@
+// build() reports a missing field through a generated FooBuilderError enum
+// that can be matched on. With #[builder(error = "...")] the builder returns a
+// user-provided error type instead, converted through From<FooBuilderError>.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    current_dir: Option<String>,
+}
+
+#[derive(Builder, Debug)]
+#[builder(error = "ConfigError")]
+pub struct Server {
+    host: String,
+    port: u16,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    Builder(ServerBuilderError),
+}
+
+impl From<ServerBuilderError> for ConfigError {
+    fn from(error: ServerBuilderError) -> Self {
+        ConfigError::Builder(error)
+    }
+}
+
+fn main() {
+    let error = match Command::builder().current_dir("..".to_owned()).build() {
+        Ok(_) => panic!("executable was never set"),
+        Err(error) => error,
+    };
+    assert_eq!(
+        error,
+        CommandBuilderError::MissingFields {
+            fields: vec!["executable"]
+        }
+    );
+    assert_eq!(error.to_string(), "executable is not set");
+
+    let error: Box<dyn std::error::Error> = Box::new(error);
+    assert_eq!(error.to_string(), "executable is not set");
+
+    let error = Server::builder().host("localhost".to_owned()).build();
+    assert_eq!(
+        error.err(),
+        Some(ConfigError::Builder(ServerBuilderError::MissingFields {
+            fields: vec!["port"]
+        }))
+    );
+}
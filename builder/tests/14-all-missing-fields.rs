@@ -0,0 +1,40 @@
+// build() doesn't stop at the first unset field: every missing required field
+// is reported in a single error, in declaration order.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Server {
+    host: String,
+    #[builder(each = "alias")]
+    aliases: Vec<String>,
+    port: u16,
+    timeout: Option<u32>,
+    workers: usize,
+}
+
+fn main() {
+    let error = match Server::builder().port(8080).build() {
+        Ok(_) => panic!("host and workers were never set"),
+        Err(error) => error,
+    };
+    assert_eq!(
+        error,
+        ServerBuilderError::MissingFields {
+            fields: vec!["host", "workers"]
+        }
+    );
+    assert_eq!(error.to_string(), "host, workers are not set");
+
+    let server = Server::builder()
+        .host("localhost".to_owned())
+        .port(8080)
+        .workers(4)
+        .build()
+        .unwrap();
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.port, 8080);
+    assert_eq!(server.workers, 4);
+    assert!(server.aliases.is_empty());
+    assert!(server.timeout.is_none());
+}
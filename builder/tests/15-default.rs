@@ -0,0 +1,97 @@
+// Fields marked #[builder(default)] or #[builder(default = "expr")] are no
+// longer required: an unset field falls back to Default::default() or to the
+// given expression, which is only evaluated when the field was not set. With
+// #[builder(default)] on the struct itself, unset fields are taken from the
+// struct's own Default impl instead.
+
+use derive_builder::Builder;
+use std::cell::Cell;
+
+thread_local! {
+    static EVALUATED: Cell<u32> = Cell::new(0);
+}
+
+fn default_workers() -> usize {
+    EVALUATED.with(|count| count.set(count.get() + 1));
+    4
+}
+
+#[derive(Builder)]
+pub struct Server {
+    host: String,
+    #[builder(default)]
+    port: u16,
+    #[builder(default = "default_workers()")]
+    workers: usize,
+    #[builder(default = "Some(30)")]
+    timeout: Option<u32>,
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(default)]
+pub struct Limits {
+    memory: u64,
+    #[builder(default = "1")]
+    cpus: u32,
+    label: Option<String>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            memory: 512,
+            cpus: 8,
+            label: Some("small".to_owned()),
+        }
+    }
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Job {
+    name: String,
+    #[builder(default = "3")]
+    retries: u32,
+}
+
+fn main() {
+    let server = Server::builder()
+        .host("localhost".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.port, 0);
+    assert_eq!(server.workers, 4);
+    assert_eq!(server.timeout, Some(30));
+    assert_eq!(EVALUATED.with(Cell::get), 1);
+
+    let server = Server::builder()
+        .host("localhost".to_owned())
+        .workers(16)
+        .timeout(5)
+        .build()
+        .unwrap();
+    assert_eq!(server.workers, 16);
+    assert_eq!(server.timeout, Some(5));
+    assert_eq!(EVALUATED.with(Cell::get), 1);
+
+    let error = match Server::builder().build() {
+        Ok(_) => panic!("host was never set"),
+        Err(error) => error,
+    };
+    assert_eq!(error.to_string(), "host is not set");
+
+    let limits = Limits::builder().memory(1024).build().unwrap();
+    assert_eq!(
+        limits,
+        Limits {
+            memory: 1024,
+            cpus: 1,
+            label: Some("small".to_owned()),
+        }
+    );
+
+    let job = Job::builder().name("backup".to_owned()).build();
+    assert_eq!(job.name, "backup");
+    assert_eq!(job.retries, 3);
+}
@@ -0,0 +1,65 @@
+// Setters can be customized per field with #[builder(setter(...))]:
+//
+//   - `into` makes the setter take `impl Into<T>`
+//   - `strip_option = false` makes an Option<T> field's setter take Option<T>
+//   - `name = "..."` renames the setter
+//   - `skip` generates no setter at all, so the field needs a default
+//
+// At the struct level, `setter(prefix = "...")` prefixes every setter derived
+// from a field name and `setter(into)` applies `into` to every setter.
+
+use derive_builder::Builder;
+
+#[derive(Builder, Debug, PartialEq)]
+pub struct Command {
+    #[builder(setter(into))]
+    executable: String,
+    #[builder(each = "arg", setter(into))]
+    args: Vec<String>,
+    #[builder(setter(strip_option = false))]
+    current_dir: Option<String>,
+    #[builder(setter(name = "envs"))]
+    env: Vec<(String, String)>,
+    #[builder(setter(skip), default = "42")]
+    id: u32,
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(setter(into, prefix = "with_"))]
+pub struct Server {
+    host: String,
+    port: Option<u16>,
+    #[builder(setter(name = "r#type"))]
+    kind: String,
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo")
+        .arg("build")
+        .arg(String::from("--release"))
+        .current_dir(None)
+        .envs(vec![("RUST_LOG".to_owned(), "debug".to_owned())])
+        .build()
+        .unwrap();
+    assert_eq!(
+        command,
+        Command {
+            executable: "cargo".to_owned(),
+            args: vec!["build".to_owned(), "--release".to_owned()],
+            current_dir: None,
+            env: vec![("RUST_LOG".to_owned(), "debug".to_owned())],
+            id: 42,
+        }
+    );
+
+    let server = Server::builder()
+        .with_host("localhost")
+        .with_port(8080_u16)
+        .r#type("http")
+        .build()
+        .unwrap();
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.port, Some(8080));
+    assert_eq!(server.kind, "http");
+}
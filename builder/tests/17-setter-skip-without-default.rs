@@ -0,0 +1,13 @@
+// A field whose setter is skipped can never be set, so it has to come from a
+// default.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    #[builder(setter(skip))]
+    id: u32,
+}
+
+fn main() {}
@@ -0,0 +1,45 @@
+// #[builder(setter(try_into))] adds a fallible try_<field>() setter next to
+// the regular one. It accepts anything implementing TryInto<T> and surfaces
+// the conversion error at the call site.
+
+use derive_builder::Builder;
+use std::num::TryFromIntError;
+
+#[derive(Builder)]
+pub struct Server {
+    host: String,
+    #[builder(setter(try_into))]
+    port: u16,
+    #[builder(setter(try_into))]
+    workers: Option<u8>,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Listener {
+    #[builder(setter(try_into))]
+    port: u16,
+}
+
+fn main() -> Result<(), TryFromIntError> {
+    let untrusted: u64 = 8080;
+    let server = Server::builder()
+        .host("localhost".to_owned())
+        .try_port(untrusted)?
+        .try_workers(16_i32)?
+        .build()
+        .unwrap();
+    assert_eq!(server.port, 8080);
+    assert_eq!(server.workers, Some(16));
+
+    let mut builder = Server::builder();
+    assert!(builder.try_port(70_000_u64).is_err());
+    assert!(builder.try_workers(-1).is_err());
+    builder.port(80);
+    assert_eq!(builder.host("localhost".to_owned()).build().unwrap().port, 80);
+
+    let listener = Listener::builder().try_port(443_u64)?.build();
+    assert_eq!(listener.port, 443);
+
+    Ok(())
+}
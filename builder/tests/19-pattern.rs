@@ -0,0 +1,61 @@
+// #[builder(pattern = "...")] picks how setters and build() take the builder:
+//
+//   - "mutable" (the default): setters take and return &mut Self
+//   - "owned": setters take and return self, and build(self) moves every
+//     field out of the builder, so field types don't need to be Clone
+//   - "immutable": setters take &self and return a modified copy
+
+use derive_builder::Builder;
+
+// Not Clone.
+#[derive(Debug)]
+pub struct Connection {
+    id: u32,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct Client {
+    connection: Connection,
+    #[builder(each = "header")]
+    headers: Vec<String>,
+    #[builder(setter(try_into))]
+    retries: Option<u8>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "immutable")]
+pub struct Request {
+    method: String,
+    path: String,
+}
+
+// A field called `builder` doesn't clash with the copy the setter makes.
+#[derive(Builder)]
+#[builder(pattern = "immutable")]
+pub struct Stage {
+    builder: String,
+}
+
+fn main() {
+    let client = Client::builder()
+        .connection(Connection { id: 7 })
+        .header("accept: */*".to_owned())
+        .try_retries(3_u32)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(client.connection.id, 7);
+    assert_eq!(client.headers, vec!["accept: */*"]);
+    assert_eq!(client.retries, Some(3));
+
+    let get = Request::builder().method("GET".to_owned());
+    let index = get.path("/".to_owned());
+    let health = get.path("/health".to_owned());
+    assert_eq!(index.build().unwrap().path, "/");
+    assert_eq!(health.build().unwrap().path, "/health");
+    assert!(get.build().is_err());
+
+    let stage = Stage::builder().builder("docker".to_owned());
+    assert_eq!(stage.build().unwrap().builder, "docker");
+}
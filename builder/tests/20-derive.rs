@@ -0,0 +1,57 @@
+// The builder derives nothing on its own, so field types don't need to be
+// Debug or Clone. #[builder(derive(...))] asks for the derives you want.
+//
+// In the default mutable pattern build() clones the fields, so a field that is
+// not Clone only makes build() unavailable; the builder and its setters are
+// still generated.
+
+use derive_builder::Builder;
+
+// Neither Debug nor Clone.
+pub struct Handle {
+    fd: i32,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct Resource {
+    handle: Handle,
+    name: String,
+}
+
+#[derive(Builder)]
+pub struct Connection {
+    handle: Handle,
+    name: String,
+}
+
+#[derive(Builder)]
+#[builder(derive(Debug, Clone, PartialEq))]
+pub struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn main() {
+    let resource = Resource::builder()
+        .handle(Handle { fd: 3 })
+        .name("stdin".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(resource.handle.fd, 3);
+    assert_eq!(resource.name, "stdin");
+
+    let mut connection = Connection::builder();
+    connection.handle(Handle { fd: 4 }).name("socket".to_owned());
+
+    let mut builder = Point::builder();
+    builder.x(1);
+    let copy = builder.clone();
+    assert_eq!(builder, copy);
+    assert_eq!(format!("{:?}", copy), "PointBuilder { x: Some(1), y: None }");
+
+    builder.y(2);
+    assert_ne!(builder, copy);
+    let point = builder.build().unwrap();
+    assert_eq!((point.x, point.y), (1, 2));
+}
@@ -0,0 +1,124 @@
+// #[derive(Builder)] on an enum generates one builder per struct-like
+// variant. MyEnum::variant_a() returns a MyEnumVariantABuilder, and its
+// build() produces MyEnum::VariantA { .. }. Fields behave just like they do
+// on a struct: each, default and Option<T> all work the same way.
+//
+// Tuple and unit variants don't get a builder.
+//
+// A variant doesn't have to use every generic parameter of the enum; its
+// builder still carries all of them. Constructor names split words the way
+// you'd write them by hand, so HTTPRequest gets http_request(). A name that
+// is a keyword is made a raw identifier, so Move gets r#move().
+
+use derive_builder::Builder;
+
+#[derive(Debug, PartialEq, Builder)]
+pub enum Message {
+    Request {
+        id: u64,
+        method: String,
+        #[builder(each = "param")]
+        params: Vec<String>,
+        timeout: Option<u32>,
+    },
+    Response {
+        id: u64,
+        #[builder(default = "200")]
+        status: u16,
+    },
+    Ping(u64),
+    Shutdown,
+}
+
+#[derive(Debug, PartialEq, Builder)]
+#[builder(typestate)]
+pub enum Shape<T> {
+    Circle { radius: T },
+    Rect { width: T, height: T },
+}
+
+#[derive(Debug, PartialEq, Builder)]
+pub enum Event<'a, T> {
+    Tick { at: u64 },
+    Data { payload: T, label: &'a str },
+    HTTPRequest { url: &'a str },
+}
+
+#[derive(Debug, PartialEq, Builder)]
+#[builder(typestate)]
+pub enum Job<'a, T> {
+    Idle { since: u64 },
+    Run { input: T, name: &'a str },
+}
+
+#[derive(Debug, PartialEq, Builder)]
+pub enum Cmd {
+    Move { x: i32 },
+    Type { name: String },
+}
+
+fn main() {
+    let request = Message::request()
+        .id(1)
+        .method("get".to_owned())
+        .param("a".to_owned())
+        .param("b".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(
+        request,
+        Message::Request {
+            id: 1,
+            method: "get".to_owned(),
+            params: vec!["a".to_owned(), "b".to_owned()],
+            timeout: None,
+        }
+    );
+
+    let response: Message = Message::response().id(1).build().unwrap();
+    assert_eq!(response, Message::Response { id: 1, status: 200 });
+
+    let err = Message::request().id(2).build().unwrap_err();
+    assert_eq!(
+        err,
+        MessageRequestBuilderError::MissingFields {
+            fields: vec!["method"]
+        }
+    );
+
+    let mut builder: MessageResponseBuilder = Message::response();
+    builder.id(3).status(404);
+    assert_eq!(
+        builder.build().unwrap(),
+        Message::Response { id: 3, status: 404 }
+    );
+
+    let circle = Shape::circle().radius(1.5).build();
+    assert_eq!(circle, Shape::Circle { radius: 1.5 });
+    let rect = Shape::rect().height(2).width(3).build();
+    assert_eq!(rect, Shape::Rect { width: 3, height: 2 });
+
+    let tick: Event<String> = Event::tick().at(5).build().unwrap();
+    assert_eq!(tick, Event::Tick { at: 5 });
+    let data = Event::data().payload(7).label("seven").build().unwrap();
+    assert_eq!(data, Event::Data { payload: 7, label: "seven" });
+    let request: Event<()> = Event::http_request().url("/").build().unwrap();
+    assert_eq!(request, Event::HTTPRequest { url: "/" });
+    let mut merged: EventTickBuilder<()> = Event::tick();
+    let mut other = Event::tick();
+    other.at(1);
+    merged.merge(other);
+    assert_eq!(merged.build().unwrap(), Event::Tick { at: 1 });
+
+    let idle: Job<String> = Job::idle().since(9).build();
+    assert_eq!(idle, Job::Idle { since: 9 });
+    let run = Job::run().name("add").input(1).build();
+    assert_eq!(run, Job::Run { input: 1, name: "add" });
+
+    let step = Cmd::r#move().x(2).build().unwrap();
+    assert_eq!(step, Cmd::Move { x: 2 });
+    let typed = Cmd::r#type().name("ls".to_owned()).build().unwrap();
+    assert_eq!(typed, Cmd::Type { name: "ls".to_owned() });
+
+    let _ = (Message::Ping(0), Message::Shutdown);
+}
@@ -0,0 +1,39 @@
+// Tuple structs get a builder too. Positional fields have setters named
+// _0, _1, ... by default, and #[builder(setter(name = "..."))] gives a field
+// a proper name.
+
+use derive_builder::Builder;
+
+#[derive(Debug, PartialEq, Builder)]
+pub struct Rgb(u8, u8, u8);
+
+#[derive(Debug, PartialEq, Builder)]
+pub struct Span(
+    #[builder(setter(name = "file"))] String,
+    #[builder(setter(name = "start"))] usize,
+    #[builder(setter(name = "end"))] usize,
+    #[builder(default)] Option<String>,
+);
+
+#[derive(Debug, PartialEq, Default, Builder)]
+#[builder(typestate, default)]
+pub struct Version(u32, u32, u32);
+
+fn main() {
+    let color = Rgb::builder()._0(255)._1(128)._2(0).build().unwrap();
+    assert_eq!(color, Rgb(255, 128, 0));
+
+    let err = Rgb::builder()._1(0).build().unwrap_err();
+    assert_eq!(err.to_string(), "_0, _2 are not set");
+
+    let span = Span::builder()
+        .file("main.rs".to_owned())
+        .start(3)
+        .end(7)
+        .build()
+        .unwrap();
+    assert_eq!(span, Span("main.rs".to_owned(), 3, 7, None));
+
+    let version = Version::builder()._0(1)._2(4).build();
+    assert_eq!(version, Version(1, 0, 4));
+}
@@ -0,0 +1,79 @@
+// #[builder(each = "...")] isn't limited to Vec<T>. Maps take the key and the
+// value as two arguments, sets and deques take one item, and any collection
+// that implements Default + Extend<Item> works once its item type is given
+// with #[builder(each(name = "...", item = "Type"))].
+
+use derive_builder::Builder;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags(Vec<String>);
+
+impl Extend<String> for Tags {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+#[derive(Builder)]
+pub struct Job {
+    #[builder(each = "env")]
+    envs: HashMap<String, String>,
+    #[builder(each = "label", setter(into))]
+    labels: BTreeMap<String, u32>,
+    #[builder(each = "port")]
+    ports: HashSet<u16>,
+    #[builder(each = "user")]
+    users: BTreeSet<String>,
+    #[builder(each = "step")]
+    steps: VecDeque<String>,
+    #[builder(each = "header")]
+    headers: std::collections::HashMap<String, String>,
+    #[builder(each(name = "tag", item = "String"))]
+    tags: Tags,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Query {
+    table: String,
+    #[builder(each = "filter")]
+    filters: BTreeMap<String, i64>,
+}
+
+fn main() {
+    let mut builder = Job::builder();
+    builder
+        .env("PATH".to_owned(), "/bin".to_owned())
+        .env("HOME".to_owned(), "/root".to_owned())
+        .label("tier", 1_u32)
+        .port(80)
+        .port(80)
+        .port(443)
+        .user("b".to_owned())
+        .user("a".to_owned())
+        .step("fetch".to_owned())
+        .step("build".to_owned())
+        .header("accept".to_owned(), "*/*".to_owned())
+        .tag("nightly".to_owned());
+    let job = builder.build().unwrap();
+
+    assert_eq!(job.envs.len(), 2);
+    assert_eq!(job.envs["HOME"], "/root");
+    assert_eq!(job.labels["tier"], 1);
+    assert_eq!(job.ports.len(), 2);
+    assert_eq!(
+        job.users.into_iter().collect::<Vec<_>>(),
+        ["a".to_owned(), "b".to_owned()]
+    );
+    assert_eq!(job.steps.front().map(String::as_str), Some("fetch"));
+    assert_eq!(job.headers["accept"], "*/*");
+    assert_eq!(job.tags, Tags(vec!["nightly".to_owned()]));
+
+    let query = Query::builder()
+        .filter("age".to_owned(), 30)
+        .table("users".to_owned())
+        .build();
+    assert_eq!(query.table, "users");
+    assert_eq!(query.filters["age"], 30);
+}
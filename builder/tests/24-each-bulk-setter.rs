@@ -0,0 +1,48 @@
+// A field with #[builder(each = "arg")] keeps its whole-collection setter
+// next to the one-at-a-time setter: args(..) replaces the collection,
+// arg(..) pushes one item and extend_args(..) appends everything from an
+// iterator. The whole-collection setter is only left out when its name
+// would collide with the one-at-a-time setter.
+
+use derive_builder::Builder;
+use std::collections::BTreeMap;
+
+#[derive(Builder)]
+pub struct Command {
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    #[builder(each = "env")]
+    env: BTreeMap<String, String>,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Batch {
+    name: String,
+    #[builder(each = "id")]
+    ids: Vec<u32>,
+}
+
+fn main() {
+    let mut builder = Command::builder();
+    builder
+        .arg("stale".to_owned())
+        .args(vec!["build".to_owned()])
+        .arg("--release".to_owned())
+        .extend_args(["--locked".to_owned(), "-q".to_owned()])
+        .env("A".to_owned(), "1".to_owned())
+        .extend_env([("B".to_owned(), "2".to_owned())]);
+    let command = builder.build().unwrap();
+    assert_eq!(command.args, ["build", "--release", "--locked", "-q"]);
+    assert_eq!(command.env.len(), 2);
+    assert_eq!(command.env["B"], "2");
+
+    let batch = Batch::builder()
+        .ids(vec![1, 2])
+        .id(3)
+        .extend_ids(4..6)
+        .name("nightly".to_owned())
+        .build();
+    assert_eq!(batch.name, "nightly");
+    assert_eq!(batch.ids, [1, 2, 3, 4, 5]);
+}
@@ -0,0 +1,99 @@
+// build() can enforce invariants before handing out the value.
+//
+// A field-level #[builder(validate = "fn")] is called with a reference to the
+// field's final value, after defaults are applied (so a &String field can be
+// checked by a fn taking &str). Its Err(String) becomes
+// FooBuilderError::InvalidField with the field name attached.
+//
+// A struct-level #[builder(validate = "path::to::fn")] is called with &Foo
+// once every field is in place, which is where cross-field constraints like
+// "min <= max" belong. Its Err(String) becomes FooBuilderError::Validation.
+
+use derive_builder::Builder;
+
+mod rules {
+    pub fn range(range: &super::Range) -> Result<(), String> {
+        if range.min <= range.max {
+            Ok(())
+        } else {
+            Err(format!("min {} is greater than max {}", range.min, range.max))
+        }
+    }
+}
+
+fn non_empty(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        Err("must not be empty".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+fn positive(step: &i32) -> Result<(), String> {
+    if *step > 0 {
+        Ok(())
+    } else {
+        Err(format!("{} is not positive", step))
+    }
+}
+
+#[derive(Debug, Builder)]
+#[builder(validate = "rules::range")]
+pub struct Range {
+    #[builder(validate = "non_empty")]
+    name: String,
+    min: i32,
+    max: i32,
+    #[builder(default = "1", validate = "positive")]
+    step: i32,
+}
+
+fn main() {
+    let range = Range::builder()
+        .name("r".to_owned())
+        .min(1)
+        .max(5)
+        .build()
+        .unwrap();
+    assert_eq!(range.name, "r");
+    assert_eq!((range.min, range.max, range.step), (1, 5, 1));
+
+    let err = Range::builder()
+        .name(String::new())
+        .min(1)
+        .max(5)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        RangeBuilderError::InvalidField {
+            field: "name",
+            message: "must not be empty".to_owned(),
+        }
+    );
+    assert_eq!(err.to_string(), "name is invalid: must not be empty");
+
+    let err = Range::builder()
+        .name("r".to_owned())
+        .min(1)
+        .max(5)
+        .step(0)
+        .build()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "step is invalid: 0 is not positive");
+
+    let err = Range::builder()
+        .name("r".to_owned())
+        .min(9)
+        .max(5)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        RangeBuilderError::Validation("min 9 is greater than max 5".to_owned())
+    );
+
+    // Missing fields are still reported before any validation runs.
+    let err = Range::builder().name(String::new()).build().unwrap_err();
+    assert_eq!(err.to_string(), "min, max are not set");
+}
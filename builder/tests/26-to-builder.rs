@@ -0,0 +1,51 @@
+// An existing value can be turned back into a builder with every field
+// already set, so a modified copy only needs the fields that change.
+//
+// From<Foo> for FooBuilder moves the fields into the builder, and
+// Foo::to_builder(&self) does the same with a clone of self, so it's available
+// whenever Foo is Clone.
+
+use derive_builder::Builder;
+
+#[derive(Debug, Clone, PartialEq, Builder)]
+pub struct Config {
+    host: String,
+    port: u16,
+    #[builder(each = "feature")]
+    features: Vec<String>,
+    timeout: Option<u32>,
+    #[builder(setter(skip), default = "7")]
+    generation: u32,
+}
+
+#[derive(Debug, PartialEq, Builder)]
+#[builder(typestate)]
+pub struct Point(i32, i32);
+
+fn main() {
+    let config = Config::builder()
+        .host("localhost".to_owned())
+        .port(8080)
+        .feature("tls".to_owned())
+        .timeout(30)
+        .build()
+        .unwrap();
+
+    let copy = config.to_builder().port(8081).build().unwrap();
+    assert_eq!(copy.host, "localhost");
+    assert_eq!(copy.port, 8081);
+    assert_eq!(copy.features, ["tls"]);
+    assert_eq!(copy.timeout, Some(30));
+    assert_eq!(config.port, 8080);
+
+    let mut builder = ConfigBuilder::from(config);
+    builder.feature("http2".to_owned());
+    let moved = builder.build().unwrap();
+    assert_eq!(moved.features, ["tls", "http2"]);
+    assert_eq!(moved.generation, 7);
+
+    // A typestate builder comes back with every field already Set, so it can
+    // build right away.
+    let point = PointBuilder::from(Point(1, 2))._1(5).build();
+    assert_eq!(point, Point(1, 5));
+}
@@ -0,0 +1,135 @@
+// #[builder(sub_builder)] on a field whose type also derives Builder keeps a
+// BarBuilder inside the FooBuilder instead of an Option<Bar>. It can be
+// reached with bar_mut() or configured through a closure with bar(|b| ..),
+// and build() builds it along with everything else. is_set_bar() tells
+// whether any field of the nested builder has been set.
+//
+// Errors from the nested build() come back wrapped in a variant named after
+// the field, FooBuilderError::Bar(BarBuilderError), and their message is
+// prefixed with the field path, like "bar.x is not set".
+//
+// The nested builder is taken to be BarBuilder next to Bar. If Bar renames its
+// builder with #[builder(name = "...")], name it with
+// sub_builder(builder = "..."). A custom #[builder(error = "...")] on Bar
+// doesn't matter here: the nested error is always Bar's generated one.
+
+use derive_builder::Builder;
+
+mod net {
+    use derive_builder::Builder;
+
+    #[derive(Debug, PartialEq, Builder)]
+    pub struct Endpoint {
+        pub host: String,
+        pub port: u16,
+        #[builder(default = "30")]
+        pub timeout: u32,
+    }
+}
+
+mod store {
+    use derive_builder::Builder;
+    use std::fmt;
+
+    #[derive(Debug, PartialEq)]
+    pub struct StoreError(pub String);
+
+    impl From<PoolFactoryError> for StoreError {
+        fn from(error: PoolFactoryError) -> Self {
+            StoreError(error.to_string())
+        }
+    }
+
+    impl fmt::Display for StoreError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Builder)]
+    #[builder(name = "PoolFactory", error = "StoreError")]
+    pub struct Pool {
+        pub size: u32,
+    }
+}
+
+#[derive(Debug, PartialEq, Builder)]
+pub struct Database {
+    #[builder(sub_builder(builder = "store::PoolFactory"))]
+    pool: store::Pool,
+}
+
+#[derive(Debug, PartialEq, Builder)]
+pub struct Tls {
+    cert: String,
+    #[builder(sub_builder)]
+    endpoint: net::Endpoint,
+}
+
+#[derive(Debug, PartialEq, Builder)]
+pub struct Service {
+    name: String,
+    #[builder(sub_builder)]
+    endpoint: net::Endpoint,
+    #[builder(sub_builder)]
+    tls: Tls,
+}
+
+fn main() {
+    let mut builder = Service::builder();
+    assert!(!builder.is_set_endpoint());
+    builder
+        .name("api".to_owned())
+        .endpoint(|b| b.host("localhost".to_owned()).port(8080));
+    assert!(builder.is_set_endpoint());
+    assert!(!builder.is_set_tls());
+    builder.tls_mut().cert("cert.pem".to_owned());
+    assert!(builder.is_set_tls());
+    builder
+        .tls_mut()
+        .endpoint_mut()
+        .host("tls.local".to_owned())
+        .port(443)
+        .timeout(5);
+    let service = builder.build().unwrap();
+    assert_eq!(service.endpoint.host, "localhost");
+    assert_eq!(service.endpoint.timeout, 30);
+    assert_eq!(service.tls.endpoint.port, 443);
+    assert_eq!(service.tls.endpoint.timeout, 5);
+
+    let err = Service::builder()
+        .name("api".to_owned())
+        .endpoint(|b| b.host("localhost".to_owned()))
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ServiceBuilderError::Endpoint(net::EndpointBuilderError::MissingFields {
+            fields: vec!["port"]
+        })
+    );
+    assert_eq!(err.to_string(), "endpoint.port is not set");
+
+    let mut builder = Service::builder();
+    builder.name("api".to_owned()).endpoint(|b| b.port(1).host("a".to_owned()));
+    builder.tls_mut().cert("cert.pem".to_owned());
+    let err = builder.build().unwrap_err();
+    assert_eq!(err.to_string(), "tls.endpoint.host, tls.endpoint.port are not set");
+
+    let mut builder = Database::builder();
+    builder.pool(|b| b.size(4));
+    assert_eq!(builder.build().unwrap().pool, store::Pool { size: 4 });
+    let err = Database::builder().build().unwrap_err();
+    assert_eq!(err.to_string(), "pool.size is not set");
+    assert_eq!(
+        store::Pool::builder().build().unwrap_err(),
+        store::StoreError("size is not set".to_owned())
+    );
+
+    // Turning a value back into a builder fills the nested builders too.
+    let mut builder = ServiceBuilder::from(service);
+    builder.tls(|b| b.endpoint(|b| b.port(8443)));
+    let copy = builder.build().unwrap();
+    assert_eq!(copy.tls.endpoint.host, "tls.local");
+    assert_eq!(copy.tls.endpoint.port, 8443);
+}
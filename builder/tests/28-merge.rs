@@ -0,0 +1,51 @@
+// Configuration is often layered: defaults, then a file, then the command
+// line. merge(other) folds a second, partially-filled builder into this one.
+// Every field that is set in other overrides this builder, and unset fields
+// are left alone.
+//
+// Collections with #[builder(each = "...")] append other's items by default.
+// #[builder(merge = "replace")] makes them behave like any other field. A
+// collection whose item type is given with each(item = "...") may not be
+// IntoIterator, so it is replaced unless merge = "append" is asked for.
+//
+// is_set_<field>() tells whether a field has been set so far.
+
+use derive_builder::Builder;
+
+#[derive(Debug, Builder)]
+pub struct Config {
+    host: String,
+    port: u16,
+    verbose: Option<bool>,
+    #[builder(each = "include")]
+    includes: Vec<String>,
+    #[builder(each = "feature", merge = "replace")]
+    features: Vec<String>,
+}
+
+fn main() {
+    let mut defaults = Config::builder();
+    defaults
+        .host("localhost".to_owned())
+        .port(80)
+        .include("/etc/app".to_owned())
+        .feature("basic".to_owned());
+
+    let mut file = Config::builder();
+    file.port(8080).include("~/.app".to_owned());
+    assert!(file.is_set_port());
+    assert!(!file.is_set_host());
+    assert!(file.is_set_includes());
+    assert!(!file.is_set_features());
+
+    let mut cli = Config::builder();
+    cli.verbose(true).feature("tls".to_owned()).feature("http2".to_owned());
+
+    defaults.merge(file).merge(cli);
+    let config = defaults.build().unwrap();
+    assert_eq!(config.host, "localhost");
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.verbose, Some(true));
+    assert_eq!(config.includes, ["/etc/app", "~/.app"]);
+    assert_eq!(config.features, ["tls", "http2"]);
+}
@@ -0,0 +1,44 @@
+// The builder's fields are private, so how it stores them can change without
+// breaking callers. get_<field>() returns a reference to a field's value if it
+// has been set, and clear_<field>() unsets it again.
+//
+// A typestate builder has getters for every field, but clear_<field>() only
+// for fields that are not required, because unsetting a required field would
+// change the builder's type.
+
+use derive_builder::Builder;
+
+#[derive(Debug, Builder)]
+pub struct Server {
+    host: String,
+    port: u16,
+    #[builder(each = "alias")]
+    aliases: Vec<String>,
+}
+
+#[derive(Debug, Builder)]
+#[builder(typestate)]
+pub struct Point(i32, i32, Option<String>);
+
+fn main() {
+    let mut builder = Server::builder();
+    assert_eq!(builder.get_host(), None);
+
+    builder.host("localhost".to_owned()).port(80).alias("www".to_owned());
+    assert_eq!(builder.get_host().map(String::as_str), Some("localhost"));
+    assert_eq!(builder.get_port(), Some(&80));
+    assert_eq!(builder.get_aliases().map(Vec::len), Some(1));
+
+    builder.clear_port().clear_aliases();
+    assert_eq!(builder.get_port(), None);
+    assert!(!builder.is_set_aliases());
+    assert_eq!(builder.build().unwrap_err().to_string(), "port is not set");
+
+    let builder = Point::builder()._0(1)._2("origin".to_owned());
+    assert_eq!(builder.get_0(), Some(&1));
+    assert_eq!(builder.get_1(), None);
+    let builder = builder.clear_2();
+    assert_eq!(builder.get_2(), None);
+    let point = builder._1(2).build();
+    assert_eq!((point.0, point.1, point.2), (1, 2, None));
+}
@@ -0,0 +1,46 @@
+// #[builder(name = "...")] renames the builder, and its error type follows
+// along as <name>Error. #[builder(vis = "...")] sets the visibility of the
+// builder, its error type and Foo::builder(), so a public type can keep its
+// builder to the crate. #[builder(setter(vis = "..."))] does the same for the
+// methods that set one field.
+
+use derive_builder::Builder;
+
+mod auth {
+    use derive_builder::Builder;
+
+    #[derive(Debug, Builder)]
+    #[builder(name = "TokenFactory", vis = "pub(crate)")]
+    pub struct Token {
+        pub value: String,
+        // Only this module decides when a token was issued.
+        #[builder(setter(vis = ""))]
+        pub issued_at: u64,
+    }
+
+    impl TokenFactory {
+        pub(crate) fn issued_now(&mut self) -> &mut Self {
+            self.issued_at(1_700_000_000)
+        }
+    }
+}
+
+#[derive(Debug, Builder)]
+#[builder(typestate, name = "Launch")]
+pub struct Rocket {
+    stages: u8,
+}
+
+fn main() {
+    let mut factory: auth::TokenFactory = auth::Token::builder();
+    factory.value("secret".to_owned()).issued_now();
+    let token = factory.build().unwrap();
+    assert_eq!(token.value, "secret");
+    assert_eq!(token.issued_at, 1_700_000_000);
+
+    let err: auth::TokenFactoryError = auth::Token::builder().build().unwrap_err();
+    assert_eq!(err.to_string(), "value, issued_at are not set");
+
+    let launch: Launch<LaunchMissingStages> = Rocket::builder();
+    assert_eq!(launch.stages(2).build().stages, 2);
+}
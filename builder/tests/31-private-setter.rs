@@ -0,0 +1,18 @@
+// A setter with #[builder(setter(vis = ""))] is private to the module that
+// defines the struct.
+
+mod auth {
+    use derive_builder::Builder;
+
+    #[derive(Builder)]
+    pub struct Token {
+        pub value: String,
+        #[builder(setter(vis = ""))]
+        pub issued_at: u64,
+    }
+}
+
+fn main() {
+    let mut builder = auth::Token::builder();
+    builder.value("secret".to_owned()).issued_at(0);
+}
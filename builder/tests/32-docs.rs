@@ -0,0 +1,93 @@
+// Every public item the derive generates is documented, so a crate that
+// denies missing docs can still derive Builder. Setters repeat the field's own
+// doc comment and say what happens when the field is left unset, and the
+// builder and build() list the fields that must be set.
+
+#![deny(missing_docs)]
+
+//! Docs for generated builders.
+
+use derive_builder::Builder;
+
+/// Where to listen.
+#[derive(Debug, Clone, Builder)]
+pub struct Address {
+    /// Host name or IP address.
+    pub host: String,
+    /// TCP port.
+    #[builder(default = "8080")]
+    pub port: u16,
+}
+
+/// A server.
+#[derive(Debug, Clone, Builder)]
+#[builder(validate = "Server::check")]
+pub struct Server {
+    /// Name shown in logs.
+    #[builder(setter(into))]
+    pub name: String,
+    /// Worker threads.
+    #[builder(setter(try_into), validate = "Server::check_workers")]
+    pub workers: u8,
+    /// Allowed origins.
+    #[builder(each = "origin")]
+    pub origins: Vec<String>,
+    /// Optional banner.
+    pub banner: Option<String>,
+    /// Listening address.
+    #[builder(sub_builder)]
+    pub address: Address,
+}
+
+impl Server {
+    fn check(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn check_workers(workers: &u8) -> Result<(), String> {
+        if *workers == 0 {
+            Err("must not be zero".to_owned())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A point.
+#[derive(Debug, Builder)]
+#[builder(typestate)]
+pub struct Point(
+    /// X coordinate.
+    pub i32,
+    /// Y coordinate.
+    #[builder(default)]
+    pub i32,
+);
+
+/// A shape.
+#[derive(Debug, Builder)]
+pub enum Shape {
+    /// A circle.
+    Circle {
+        /// Radius.
+        radius: f64,
+    },
+}
+
+fn main() {
+    let server = Server::builder()
+        .name("api")
+        .try_workers(4u32)
+        .unwrap()
+        .origin("example.com".to_owned())
+        .address(|address| address.host("localhost".to_owned()))
+        .build()
+        .unwrap();
+    assert_eq!(server.address.port, 8080);
+
+    let point = Point::builder()._0(1).build();
+    assert_eq!((point.0, point.1), (1, 0));
+
+    let Shape::Circle { radius } = Shape::circle().radius(1.5).build().unwrap();
+    assert_eq!(radius, 1.5);
+}
@@ -0,0 +1,19 @@
+// Every mistake in the builder attributes is reported at once, each pointing
+// at the attribute that caused it, rather than stopping at the first one.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(patern = "owned")]
+pub struct Command {
+    #[builder(each = "arg")]
+    executable: String,
+    #[builder(default, default = "Vec::new()")]
+    args: Vec<String>,
+    #[builder(each = "var", sub_builder)]
+    env: Vec<String>,
+    #[builder(setter(skip), merge = "replace")]
+    current_dir: Option<String>,
+}
+
+fn main() {}
@@ -0,0 +1,72 @@
+// Option fields are recognized however the path to std's Option is written,
+// while an Option from some other module is a plain required field. Type
+// aliases and shadowed names hide what they stand for, so #[builder(optional)]
+// and #[builder(required)] say what the macro cannot see.
+
+use derive_builder::Builder;
+
+type MaybePort = Option<u16>;
+
+#[derive(Debug, Builder)]
+pub struct Command {
+    executable: String,
+    current_dir: std::option::Option<String>,
+    timeout: ::core::option::Option<u32>,
+    #[builder(optional)]
+    port: MaybePort,
+    #[builder(required)]
+    nice: Option<i8>,
+    priority: custom::Option<u8>,
+}
+
+mod custom {
+    use derive_builder::Builder;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Option<T>(pub T);
+
+    #[derive(Debug, Builder)]
+    pub struct Job {
+        #[builder(required)]
+        pub id: Option<u32>,
+    }
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .current_dir("..".to_owned())
+        .nice(None)
+        .priority(custom::Option(1))
+        .build()
+        .unwrap();
+    assert_eq!(command.current_dir.as_deref(), Some(".."));
+    assert_eq!(command.timeout, None);
+    assert_eq!(command.port, None);
+    assert_eq!(command.nice, None);
+
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .timeout(30)
+        .port(Some(8080))
+        .nice(Some(5))
+        .priority(custom::Option(2))
+        .build()
+        .unwrap();
+    assert_eq!(command.timeout, Some(30));
+    assert_eq!(command.port, Some(8080));
+
+    let err = Command::builder()
+        .executable("cargo".to_owned())
+        .build()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "nice, priority are not set");
+
+    let err = custom::Job::builder().build().unwrap_err();
+    assert_eq!(err.to_string(), "id is not set");
+    let job = custom::Job::builder()
+        .id(custom::Option(1))
+        .build()
+        .unwrap();
+    assert_eq!(job.id, custom::Option(1));
+}
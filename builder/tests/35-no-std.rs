@@ -0,0 +1,49 @@
+// With #[builder(no_std)] the builder and its error type only name core and
+// alloc, so they compile in a crate without std. The error type implements
+// std::error::Error only when the crate's "std" feature is enabled.
+
+#![no_std]
+
+extern crate alloc;
+// Only so this test can run; nothing generated below refers to it.
+extern crate std as host;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use derive_builder::Builder;
+
+#[derive(Debug, Builder)]
+#[builder(no_std)]
+pub struct Uart {
+    #[builder(setter(into))]
+    name: String,
+    #[builder(each = "pin")]
+    pins: Vec<u8>,
+    baud: Option<u32>,
+}
+
+#[derive(Debug, Builder)]
+#[builder(no_std, typestate)]
+pub struct Spi {
+    frequency: u32,
+    #[builder(default)]
+    mode: u8,
+}
+
+fn main() {
+    let uart = Uart::builder()
+        .name("uart0")
+        .pin(1)
+        .pin(2)
+        .build()
+        .unwrap();
+    assert_eq!(uart.name, "uart0");
+    assert_eq!(uart.pins, [1, 2]);
+    assert_eq!(uart.baud, None);
+
+    let err = Uart::builder().build().unwrap_err();
+    assert_eq!(err.to_string(), "name is not set");
+
+    let spi = Spi::builder().frequency(1_000_000).build();
+    assert_eq!((spi.frequency, spi.mode), (1_000_000, 0));
+}
@@ -0,0 +1,67 @@
+// #[builder(env)] adds apply_kv(), which sets fields from string keys and
+// values by parsing each value with FromStr, and from_env(), which does the
+// same for the environment variables that start with a prefix. A field's key
+// is its name in upper case unless #[builder(env = "...")] renames it, and
+// every value that fails to parse is reported together.
+
+use derive_builder::Builder;
+use std::collections::HashMap;
+
+#[derive(Debug, Builder)]
+#[builder(env)]
+pub struct Service {
+    host: String,
+    port: u16,
+    #[builder(env = "TIMEOUT_SECS")]
+    timeout: Option<u64>,
+    #[builder(each = "tag")]
+    tags: Vec<String>,
+    #[builder(env(skip), default)]
+    verbose: bool,
+}
+
+fn main() {
+    let mut builder = Service::builder();
+    builder
+        .apply_kv([("HOST", "localhost"), ("PORT", "8080"), ("OTHER", "x")])
+        .unwrap();
+    let service = builder.tag("web".to_owned()).build().unwrap();
+    assert_eq!(service.host, "localhost");
+    assert_eq!(service.port, 8080);
+    assert_eq!(service.timeout, None);
+    assert_eq!(service.tags, ["web"]);
+
+    let mut values = HashMap::new();
+    values.insert("TIMEOUT_SECS".to_owned(), "30".to_owned());
+    values.insert("VERBOSE".to_owned(), "true".to_owned());
+    let mut builder = Service::builder();
+    builder.apply_kv(values).unwrap();
+    assert_eq!(builder.get_timeout(), Some(&Some(30)));
+    assert!(!builder.is_set_verbose());
+
+    let err = Service::builder()
+        .apply_kv([("PORT", "http"), ("TIMEOUT_SECS", "-1"), ("HOST", "example.com")])
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ServiceBuilderError::InvalidValues {
+            errors: vec![
+                ("port", "invalid digit found in string".to_owned()),
+                ("timeout", "invalid digit found in string".to_owned()),
+            ],
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "port cannot be parsed: invalid digit found in string, timeout cannot be parsed: invalid digit found in string",
+    );
+
+    std::env::set_var("SERVICE_TEST_HOST", "0.0.0.0");
+    std::env::set_var("SERVICE_TEST_PORT", "443");
+    let service = ServiceBuilder::from_env("SERVICE_TEST_")
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(service.host, "0.0.0.0");
+    assert_eq!(service.port, 443);
+}
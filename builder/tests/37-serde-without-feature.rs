@@ -0,0 +1,12 @@
+// #[builder(serde)] is only available with the "serde" feature of
+// derive_builder.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(serde)]
+pub struct Server {
+    name: String,
+}
+
+fn main() {}
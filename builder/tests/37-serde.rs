@@ -0,0 +1,70 @@
+// With the "serde" feature, #[builder(serde)] derives Deserialize for the
+// builder. Every field may be left out of the document, so a partial config
+// only sets what it mentions; it can then be merged over other settings and
+// checked by build() like any other builder.
+
+use derive_builder::Builder;
+
+#[derive(Debug, Builder)]
+#[builder(serde)]
+pub struct Address {
+    host: String,
+    #[builder(default = "8080")]
+    port: u16,
+}
+
+#[derive(Debug, Builder)]
+#[builder(serde, validate = "Server::check")]
+pub struct Server {
+    name: String,
+    #[builder(default = "4")]
+    workers: u8,
+    #[builder(each = "origin")]
+    origins: Vec<String>,
+    banner: Option<String>,
+    #[builder(sub_builder)]
+    address: Address,
+}
+
+impl Server {
+    fn check(&self) -> Result<(), String> {
+        if self.workers == 0 {
+            Err("workers must not be zero".to_owned())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    let mut builder: ServerBuilder = serde_json::from_str(
+        r#"{"name": "api", "origins": ["a.example"], "address": {"host": "localhost"}}"#,
+    )
+    .unwrap();
+    let server = builder.origin("b.example".to_owned()).build().unwrap();
+    assert_eq!(server.name, "api");
+    assert_eq!(server.workers, 4);
+    assert_eq!(server.origins, ["a.example", "b.example"]);
+    assert_eq!(server.banner, None);
+    assert_eq!(server.address.host, "localhost");
+    assert_eq!(server.address.port, 8080);
+
+    let mut defaults = Server::builder();
+    defaults
+        .name("default".to_owned())
+        .address(|address| address.host("0.0.0.0".to_owned()));
+    let overrides: ServerBuilder =
+        serde_json::from_str(r#"{"banner": "hello", "address": {"port": 80}}"#).unwrap();
+    defaults.merge(overrides);
+    let server = defaults.build().unwrap();
+    assert_eq!(server.name, "default");
+    assert_eq!(server.banner.as_deref(), Some("hello"));
+    assert_eq!((server.address.host.as_str(), server.address.port), ("0.0.0.0", 80));
+
+    let mut builder: ServerBuilder = serde_json::from_str(r#"{"workers": 0}"#).unwrap();
+    let err = builder.build().unwrap_err();
+    assert_eq!(err.to_string(), "name is not set");
+    builder.name("api".to_owned()).address(|address| address.host("h".to_owned()));
+    let err = builder.build().unwrap_err();
+    assert_eq!(err.to_string(), "workers must not be zero");
+}
@@ -0,0 +1,14 @@
+// With #[builder(env)] every field is parsed from a string, so a field whose
+// type doesn't implement FromStr is an error pointing at that field. Mark it
+// #[builder(env(skip))] to leave it out of apply_kv().
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(env)]
+pub struct Upload {
+    name: String,
+    raw: Vec<u8>,
+}
+
+fn main() {}
@@ -0,0 +1,75 @@
+// A #[builder(sub_builder)] field works whichever pattern the nested builder
+// uses. build() and merge() on the outer builder don't depend on it, but the
+// closure setter hands over the nested builder the way that builder's own
+// setters take it, so sub_builder(pattern = "...") says which one it is:
+//
+//   - "mutable" (the default): limits(|b| b.max(10)) with b: &mut LimitsBuilder
+//   - "owned": the closure takes the builder and returns it
+//   - "immutable": the closure borrows the builder and returns a new one
+
+use derive_builder::Builder;
+
+#[derive(Debug, PartialEq, Builder)]
+#[builder(pattern = "owned")]
+pub struct Limits {
+    max: u32,
+    #[builder(default)]
+    min: u32,
+}
+
+#[derive(Debug, PartialEq, Builder)]
+#[builder(pattern = "immutable", derive(Clone))]
+pub struct Label {
+    text: String,
+}
+
+#[derive(Debug, PartialEq, Builder)]
+pub struct Gauge {
+    #[builder(sub_builder(pattern = "owned"))]
+    limits: Limits,
+    #[builder(sub_builder(pattern = "immutable"))]
+    label: Label,
+}
+
+#[derive(Debug, PartialEq, Builder)]
+#[builder(pattern = "owned")]
+pub struct Panel {
+    #[builder(sub_builder(pattern = "owned"))]
+    limits: Limits,
+}
+
+fn main() {
+    let mut builder = Gauge::builder();
+    builder
+        .limits(|b| b.max(10))
+        .label(|b| b.text("cpu".to_owned()));
+    assert_eq!(
+        builder.build().unwrap(),
+        Gauge {
+            limits: Limits { max: 10, min: 0 },
+            label: Label {
+                text: "cpu".to_owned()
+            },
+        }
+    );
+
+    let mut builder = Gauge::builder();
+    builder.label(|b| b.text("cpu".to_owned()));
+    assert_eq!(
+        builder.build().unwrap_err(),
+        GaugeBuilderError::Limits(LimitsBuilderError::MissingFields { fields: vec!["max"] })
+    );
+
+    // merge() keeps what the nested builders of `other` had set.
+    let mut merged = Gauge::builder();
+    merged.label(|b| b.text("mem".to_owned()));
+    let mut other = Gauge::builder();
+    other.limits(|b| b.max(5).min(1));
+    merged.merge(other);
+    let gauge = merged.build().unwrap();
+    assert_eq!(gauge.limits, Limits { max: 5, min: 1 });
+    assert_eq!(gauge.label.text, "mem");
+
+    let panel = Panel::builder().limits(|b| b.max(3)).build().unwrap();
+    assert_eq!(panel.limits, Limits { max: 3, min: 0 });
+}
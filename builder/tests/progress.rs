@@ -10,4 +10,37 @@ fn tests() {
     t.pass("tests/07-repeated-field.rs");
     t.compile_fail("tests/08-unrecognized-attribute.rs");
     t.pass("tests/09-redefined-prelude-types.rs");
+    t.pass("tests/10-generics.rs");
+    t.pass("tests/11-typestate.rs");
+    t.compile_fail("tests/12-typestate-missing-field.rs");
+    t.pass("tests/13-build-error.rs");
+    t.pass("tests/14-all-missing-fields.rs");
+    t.pass("tests/15-default.rs");
+    t.pass("tests/16-setter-options.rs");
+    t.compile_fail("tests/17-setter-skip-without-default.rs");
+    t.pass("tests/18-try-setter.rs");
+    t.pass("tests/19-pattern.rs");
+    t.pass("tests/20-derive.rs");
+    t.pass("tests/21-enum.rs");
+    t.pass("tests/22-tuple-struct.rs");
+    t.pass("tests/23-each-collections.rs");
+    t.pass("tests/24-each-bulk-setter.rs");
+    t.pass("tests/25-validate.rs");
+    t.pass("tests/26-to-builder.rs");
+    t.pass("tests/27-sub-builder.rs");
+    t.pass("tests/28-merge.rs");
+    t.pass("tests/29-getters.rs");
+    t.pass("tests/30-name-and-vis.rs");
+    t.compile_fail("tests/31-private-setter.rs");
+    t.pass("tests/32-docs.rs");
+    t.compile_fail("tests/33-attribute-errors.rs");
+    t.pass("tests/34-option-paths.rs");
+    t.pass("tests/35-no-std.rs");
+    t.pass("tests/36-env.rs");
+    #[cfg(feature = "serde")]
+    t.pass("tests/37-serde.rs");
+    #[cfg(not(feature = "serde"))]
+    t.compile_fail("tests/37-serde-without-feature.rs");
+    t.compile_fail("tests/38-env-not-fromstr.rs");
+    t.pass("tests/39-sub-builder-patterns.rs");
 }